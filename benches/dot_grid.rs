@@ -0,0 +1,58 @@
+// 클라이언트 Dots가 매 프레임 하는 부채꼴 조회를, 모든 점을 훑는 방식과 격자 방식으로 비교한다.
+// cargo bench --no-default-features --bench dot_grid
+
+use bug::game::grid::SpatialGrid;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use glam::Vec2;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const MAP_RADIUS: f32 = 2500.0;
+// 클라이언트 Dots::CELL_SIZE와 같은 값
+const CELL_SIZE: f32 = 128.0;
+// 가장 두꺼운 지렁이가 빨아들이는 부채꼴
+const SECTOR_RADIUS: f32 = 72.0 * 1.5;
+const SECTOR_ANGLE: f32 = std::f32::consts::PI * 5.0 / 6.0;
+
+fn random_dots(count: usize) -> Vec<(usize, Vec2)> {
+    let mut rng = StdRng::seed_from_u64(7);
+    (0..count)
+        .map(|i| {
+            let r = rng.random_range(0.0..1.0f32).sqrt() * MAP_RADIUS;
+            let theta = rng.random_range(0.0..std::f32::consts::TAU);
+            (i, Vec2::new(r * theta.cos(), r * theta.sin()))
+        })
+        .collect()
+}
+
+fn in_sector(pos: Vec2, head: Vec2, dir: Vec2, half_cos: f32) -> bool {
+    let rel = pos - head;
+    let dist = rel.length();
+    dist <= SECTOR_RADIUS && (dist <= f32::EPSILON || dir.dot(rel / dist) >= half_cos)
+}
+
+fn sector_query(c: &mut Criterion) {
+    let head = Vec2::new(300.0, -200.0);
+    let dir = Vec2::X;
+    let half_cos = (SECTOR_ANGLE * 0.5).cos();
+
+    let mut group = c.benchmark_group("sector_query");
+    for count in [200, 5_000, 50_000] {
+        let dots = random_dots(count);
+        let mut grid = SpatialGrid::new(CELL_SIZE);
+        for (id, pos) in dots.iter() {
+            grid.insert(*id, *pos);
+        }
+
+        group.bench_with_input(BenchmarkId::new("linear", count), &dots, |b, dots| {
+            b.iter(|| dots.iter().filter(|(_, pos)| in_sector(*pos, black_box(head), dir, half_cos)).count())
+        });
+        group.bench_with_input(BenchmarkId::new("grid", count), &grid, |b, grid| {
+            b.iter(|| grid.query_circle(black_box(head), SECTOR_RADIUS).filter(|(_, pos)| in_sector(*pos, head, dir, half_cos)).count())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, sector_query);
+criterion_main!(benches);
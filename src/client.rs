@@ -1,15 +1,24 @@
+mod interpolation;
+mod network_plugin;
+
 use bevy::{color::palettes::css::*, prelude::*};
 use bevy_prototype_lyon::prelude::*;
 use rand::{Rng};
-use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet, VecDeque};
 use bevy::window::PrimaryWindow;
+use crate::interpolation::{InterpolationPlugin, SnapshotBuffer};
+use crate::network_plugin::{NetworkPlugin, PlayerAction};
+use bug::game::grid::SpatialGrid;
+use bug::game::interest::camera_zoom;
+use bug::game::TICK_RATE;
+use bug::network::message::snapshot::FoodInfo;
 
 fn main() {
     let map = Map::new();
     let worm = Worm::new(map.radius);
 
     App::new()
-        .add_plugins((DefaultPlugins, ShapePlugin))
+        .add_plugins((DefaultPlugins, ShapePlugin, NetworkPlugin, InterpolationPlugin))
         .insert_resource(ClearColor(Color::srgb(0.8, 0.3, 0.3)))
         .insert_resource(map)
         .insert_resource(worm)
@@ -18,15 +27,19 @@ fn main() {
         .insert_resource(RemoteWorms::new())
         .insert_resource(Leaderboard::new(5))
         .add_systems(Startup, setup)
+        // 서버 tick과 같은 간격으로 예측해야 서버 상태로 되감은 뒤 입력을 다시 적용했을 때 결과가 같다.
+        // 접속 전에는 기본값으로 돌고, 서버가 ResWelcome으로 tick 수를 알려주면 그 값으로 바꾼다.
+        .insert_resource(Time::<Fixed>::from_hz(TICK_RATE))
         .add_systems(Update, (
             input_dir,
-            move_head,
             redraw_worm,
             check_collision,
             animate_absorbing,
             check_damage_zone,
             handle_reset,
+            despawn_dead_worm.after(handle_reset),
             check_player_death,
+            redraw_remote_worms,
             mouse_aim,
             draw_leaderboard_ui,
             camera_follow))
@@ -36,8 +49,8 @@ fn main() {
 
 #[derive(Resource, Default)]
 struct AbsorbingDots {
-    // entity, growth, elapsed, duration, start_pos
-    items: Vec<(Entity, usize, f32, f32, Vec2)>,
+    // entity, growth, food id, elapsed, duration, start_pos
+    items: Vec<(Entity, usize, Option<u32>, f32, f32, Vec2)>,
 }
 
 fn camera_follow(
@@ -52,13 +65,14 @@ fn camera_follow(
     if let Ok(mut transform) = camera_q.single_mut() {
         // smooth translation towards head using time-based exponential smoothing
         let dt = time.delta_secs();
-        let target = Vec3::new(worm.head.x, worm.head.y, transform.translation.z);
+        let head = worm.head + worm.correction;
+        let target = Vec3::new(head.x, head.y, transform.translation.z);
         let trans_alpha = 1.0 - (-8.0 * dt).exp(); // responsiveness
         transform.translation = transform.translation.lerp(target, trans_alpha);
 
         // smooth zoom out as worm grows so map remains visible (gentle, time-based)
-        let len = worm.points.len() as f32;
-        let target_zoom = (1.0 + len * 0.005).clamp(1.0, 3.0);
+        // 서버도 같은 식으로 화면 크기를 잡아서 그 근처의 지렁이와 먹이만 보내준다.
+        let target_zoom = camera_zoom(worm.points.len());
         let zoom_alpha = 1.0 - (-3.0 * dt).exp();
         transform.scale = transform.scale.lerp(Vec3::splat(target_zoom), zoom_alpha);
     }
@@ -122,6 +136,7 @@ struct Worm {
     boost_max: f32, // 최대 부스트 시간
     boost_recharge: f32, // 부스트 회복 속도
     boost_available: bool, // 완전 충전 전까지 재사용 금지
+    boost_input: bool, // 부스트 키를 누르고 있는지 (서버로 보내는 입력)
 
     points: VecDeque<Vec2>,    // 머리 위치 히스토리 = 몸통
     max_points: usize,         // 몸 길이 (샘플 수)
@@ -131,11 +146,17 @@ struct Worm {
     // --- 추가: 회전 관련 파라미터
     turn_speed: f32,
     damage_accumulator: f32,
+
+    // 서버 보정으로 위치가 튄 만큼을 화면에서만 잠시 더해 그려서 부드럽게 따라가게 한다.
+    correction: Vec2,
+    // 서버가 ResMove로 마지막에 보내준 내 몸통. ResState로 되감을 때 쓴다.
+    server_points: Vec<Vec2>,
 }
 
 #[derive(Component)]
 struct Dot {
     growth: usize,
+    id: Option<u32>,    // 서버가 준 먹이만 id가 있다. 먹으면 id로 서버에 알린다.
 }
 
 #[derive(Component)]
@@ -143,7 +164,9 @@ struct DotsShape;
 
 #[derive(Resource)]
 struct Dots {
-    items: Vec<(Vec2, Entity)>,  // Position + Entity 함께!
+    // 점이 수만 개여도 머리 근처 칸만 보도록 격자에 나눠 담는다
+    items: SpatialGrid<Entity>,
+    ids: HashMap<u32, (Entity, Vec2)>,   // 서버 먹이 id -> Entity, 위치
 }
 
 impl Dots {
@@ -152,10 +175,13 @@ impl Dots {
     // sector (fan) absorption params - made larger by default
     const SECTOR_RADIUS: f32 = 120.0;
     const SECTOR_ANGLE: f32 = std::f32::consts::FRAC_PI_2;
+    // 빨아들이는 부채꼴 반지름(최대 두께의 1.5배)보다 조금 크게
+    const CELL_SIZE: f32 = 128.0;
 
     fn new() -> Self {
         Self {
-            items: Vec::new(),
+            items: SpatialGrid::new(Self::CELL_SIZE),
+            ids: HashMap::new(),
         }
     }
 
@@ -184,7 +210,30 @@ impl Dots {
     fn spawn_at(&mut self, commands: &mut Commands, pos: Vec2) -> Entity { // [변경됨] 추가
         // Random growth amount 1~3
         let growth = rand::rng().random_range(1..=3);
-        
+        self.spawn_with_growth(commands, pos, growth, None)
+    }
+
+    /// 서버가 보내준 먹이. 이미 있는 id면 그대로 둔다.
+    fn spawn_food(&mut self, commands: &mut Commands, food: &FoodInfo) {
+        if self.ids.contains_key(&food.id) {
+            return;
+        }
+        let position = Vec2::new(food.position.0, food.position.1);
+        let entity = self.spawn_with_growth(commands, position, food.growth, Some(food.id));
+        self.ids.insert(food.id, (entity, position));
+    }
+
+    /// 다른 지렁이가 먹어서 서버가 지운 먹이. 내가 빨아들이는 중인 점은 애니메이션이 끝나면 알아서 지워진다.
+    fn remove_food(&mut self, commands: &mut Commands, id: u32) {
+        let Some((entity, position)) = self.ids.remove(&id) else {
+            return;
+        };
+        if self.items.remove(entity, position) {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    fn spawn_with_growth(&mut self, commands: &mut Commands, pos: Vec2, growth: usize, id: Option<u32>) -> Entity {
         // Size scales with growth: 1->1.0x, 2->1.2x, 3->1.4x
         let radius = Self::DOT_RADIUS * (0.8 + 0.2 * growth as f32);
 
@@ -198,24 +247,25 @@ impl Dots {
                 // render dots between worm and damage zone
                 Transform::from_translation(pos.extend(0.0)),
             DotsShape,
-            Dot { growth },
+            Dot { growth, id },
         )).id();
 
-        self.items.push((pos, entity));
+        self.items.insert(entity, pos);
         entity
     }
 
+    /// 모든 점을 지운다. (서버 스냅샷으로 다시 채우기 전)
+    fn clear(&mut self, commands: &mut Commands) {
+        for (entity, _) in self.items.iter() {
+            commands.entity(entity).despawn();
+        }
+        self.items.clear();
+        self.ids.clear();
+    }
+
     fn remove_nearby(&mut self, center: Vec2) -> Vec<Entity> {
-        let mut removed = Vec::new();
-        self.items.retain(|(pos, entity)| {
-            if pos.distance(center) <= Self::EAT_RADIUS {
-                removed.push(*entity);
-                false
-            } else {
-                true
-            }
-        });
-        removed
+        let nearby = self.items.query_circle(center, Self::EAT_RADIUS).collect::<Vec<_>>();
+        self.remove_all(nearby)
     }
 
     /// Remove dots that lie inside a sector (fan) in front of `head` along `dir`.
@@ -226,31 +276,24 @@ impl Dots {
     }
 
     fn remove_in_sector_params(&mut self, head: Vec2, dir: Vec2, radius: f32, angle: f32) -> Vec<Entity> {
-        let mut removed = Vec::new();
-
         let half_cos = (angle * 0.5).cos();
 
-        self.items.retain(|(pos, entity)| {
-            let rel = *pos - head;
-            let dist = rel.length();
-            if dist <= std::f32::EPSILON {
-                removed.push(*entity);
-                return false;
-            }
-            if dist > radius {
-                return true;
-            }
-
-            let dir_dot = dir.dot(rel / dist);
-            if dir_dot >= half_cos {
-                removed.push(*entity);
-                false
-            } else {
-                true
-            }
-        });
+        // 부채꼴을 감싸는 원 안의 칸만 본다
+        let in_sector = self.items.query_circle(head, radius)
+            .filter(|(_, pos)| {
+                let rel = *pos - head;
+                let dist = rel.length();
+                dist <= std::f32::EPSILON || dir.dot(rel / dist) >= half_cos
+            })
+            .collect::<Vec<_>>();
+        self.remove_all(in_sector)
+    }
 
-        removed
+    fn remove_all(&mut self, items: Vec<(Entity, Vec2)>) -> Vec<Entity> {
+        items.into_iter()
+            .filter(|(entity, pos)| self.items.remove(*entity, *pos))
+            .map(|(entity, _)| entity)
+            .collect()
     }
 }
 
@@ -261,7 +304,8 @@ impl Worm {
 
     fn new(map_radius: f32) -> Self {
         let mut rng = rand::rng();
-        let id = rng.random();
+        // id는 서버가 ResSession으로 정해준다. 그 전까지는 0.
+        let id = 0;
         let sample_distance = 6.0;
 
         let angle = rng.random_range(0.0..std::f32::consts::TAU);
@@ -293,12 +337,15 @@ impl Worm {
             boost_max:3.0,
             boost_recharge: 0.4,
             boost_available: true,
+            boost_input: false,
 
             points,
             max_points: Self::INITIAL_MAX_POINTS,
             sample_distance,
             turn_speed: 3.0,
             damage_accumulator: 0.0,
+            correction: Vec2::ZERO,
+            server_points: Vec::new(),
 
             is_dead: false,
         }
@@ -331,8 +378,59 @@ impl Worm {
         self.mode = SpeedMode::Nomal;
         self.boost_min = self.boost_max;
         self.boost_available = true;
+        self.correction = Vec2::ZERO;
         self.is_dead = false;
     }
+
+    // 한 tick 만큼 회전, 부스트 게이지, 이동을 처리 (서버의 WormState::step과 같은 규칙)
+    fn simulate(&mut self, dt: f32) {
+        // dir이 target_dir을 부드럽게 따라감 (slerp)
+        let t = (self.turn_speed * dt).clamp(0.0, 1.0);
+        self.dir = self.dir.slerp(self.target_dir, t);
+
+        if self.boost_input && self.boost_available && self.boost_min > 0.0 {
+            self.mode = SpeedMode::Boost;
+
+            // 남은 시간 줄이기
+            self.boost_min = (self.boost_min - dt).max(0.0);
+            if self.boost_min <= 0.0 {
+                self.boost_available = false;
+            }
+        } else {
+            // 부스트 OFF
+            self.mode = SpeedMode::Nomal;
+
+            // 부스트 회복
+            self.boost_min = (self.boost_min + self.boost_recharge * dt).min(self.boost_max);
+            if self.boost_min >= self.boost_max {
+                self.boost_available = true;
+            }
+        }
+
+        // 속도는 부스트 모드로 설정
+        let speed = match self.mode {
+            SpeedMode::Nomal => self.base_speed,
+            SpeedMode::Boost => self.boost_speed,
+        };
+
+        // Dir2는 길이가 1인 "방향"이므로, as_vec2()로 Vec2를 꺼내서 위치 계산에 사용
+        let new_head = self.head + self.dir.as_vec2() * speed * dt;
+
+        // 샘플링: 너무 촘촘하면 점이 과도하게 늘어서 지렁이가 “굵은 덩어리”처럼 보일 수 있음
+        let push = match self.points.back().copied() {
+            Some(last) => new_head.distance(last) >= self.sample_distance,
+            None => true,
+        };
+
+        self.head = new_head;
+
+        if push {
+            self.points.push_back(new_head);
+            while self.points.len() > self.max_points {
+                self.points.pop_front();
+            }
+        }
+    }
     
     fn grow(&mut self, points: usize) {
         self.max_points += points * Self::GROWTH_PER_DOT;
@@ -356,6 +454,34 @@ impl Worm {
         self.max_points > Self::MIN_POINTS
     }
 
+    // 서버가 정해준 위치에서 다시 시작 (ResJoin)
+    fn adopt_spawn(&mut self, points: Vec<Vec2>) {
+        if points.len() < 2 {
+            return;
+        }
+        let head = points[points.len() - 1];
+        if let Ok(dir) = Dir2::new(head - points[points.len() - 2]) {
+            self.dir = dir;
+            self.target_dir = dir;
+        }
+        self.head = head;
+        self.server_points = points.clone();
+        self.points = points.into();
+        self.correction = Vec2::ZERO;
+    }
+
+    // 서버가 보내준 상태로 되돌린다 (예측 보정)
+    fn rewind(&mut self, head: Vec2, dir: Vec2, boost_remaining: f32, boost_available: bool, max_points: usize) {
+        self.head = head;
+        if let Ok(dir) = Dir2::new(dir) {
+            self.dir = dir;
+        }
+        self.boost_min = boost_remaining;
+        self.boost_available = boost_available;
+        self.max_points = max_points;
+        self.points = self.server_points.iter().copied().collect();
+    }
+
     fn is_outside(&self, map: &Map) -> bool {
         self.head.length() > map.radius
     }
@@ -422,22 +548,23 @@ struct DamageZone {
     damage_per_sec: f32,
 }
 
-fn setup(mut commands: Commands, mut dots: ResMut<Dots>, map: Res<Map>, worm: Res<Worm>) {
-    commands.spawn(Camera2d);
+#[derive(Component)]
+struct MapShape;
 
-    // 게임 맵 생성
+fn spawn_map_shape(commands: &mut Commands, radius: f32) {
     let inner_circle = shapes::Circle {
-        radius: map.radius,
+        radius,
         center: Vec2::ZERO,
     };
     commands.spawn((
         ShapeBuilder::with(&inner_circle).fill(Color::srgb(0.1, 0.1, 0.15)).build(),
         Transform::from_translation(Vec3::new(0.0, 0.0, -1.0)),
+        MapShape,
     ));
+}
 
+fn spawn_damage_zone(commands: &mut Commands, pos: Vec2, radius: f32, damage_per_sec: f32) {
     let light_blue_transparent = Color::srgba(0.5, 0.8, 1.0, 0.3);
-    
-    let (pos, radius) = map.random_circle_inside(30.0, 100.0);
     let circle = shapes::Circle {
         radius,
         center: Vec2::ZERO,
@@ -448,9 +575,20 @@ fn setup(mut commands: Commands, mut dots: ResMut<Dots>, map: Res<Map>, worm: Re
         Transform::from_translation(pos.extend(-0.9)),
         DamageZone {
             radius,
-            damage_per_sec: 30.0,
+            damage_per_sec,
         },
     ));
+}
+
+fn setup(mut commands: Commands, mut dots: ResMut<Dots>, map: Res<Map>, worm: Res<Worm>) {
+    commands.spawn(Camera2d);
+
+    // 게임 맵 생성
+    // 서버에 접속하면 ResSnapshot으로 받은 맵, 데미지 영역, 점들로 다시 그린다.
+    spawn_map_shape(&mut commands, map.radius);
+
+    let (pos, radius) = map.random_circle_inside(30.0, 100.0);
+    spawn_damage_zone(&mut commands, pos, radius, 30.0);
 
     // 초기 더미 path: 실제 게임 시작 시에는 `worm` 리소스의 위치로 초기화
     let path = ShapePath::new().move_to(worm.head).line_to(worm.head + Vec2::new(1.0, 0.0));
@@ -523,13 +661,11 @@ fn draw_leaderboard_ui(
 }
 
 /// 방향 전환(키 입력). (WASD / 화살표)
-fn input_dir(keys: Res<ButtonInput<KeyCode>>, time: Res<Time>, mut worm: ResMut<Worm>) {
+fn input_dir(keys: Res<ButtonInput<KeyCode>>, mut worm: ResMut<Worm>) {
     if worm.is_dead {
         return;
     }
 
-    let dt = time.delta_secs();
-
     // 부스트 키
     let boost_key = keys.pressed(KeyCode::ShiftLeft) || keys.pressed(KeyCode::ShiftRight);
 
@@ -556,28 +692,8 @@ fn input_dir(keys: Res<ButtonInput<KeyCode>>, time: Res<Time>, mut worm: ResMut<
         }
     }
 
-    // dir이 target_dir을 부드럽게 따라감 (slerp)
-    let t = (worm.turn_speed * dt).clamp(0.0, 1.0);
-    worm.dir = worm.dir.slerp(worm.target_dir, t);
-
-    if boost_key && worm.boost_available && worm.boost_min > 0.0 {
-        worm.mode = SpeedMode::Boost;
-
-        // 남은 시간 줄이기
-        worm.boost_min = (worm.boost_min - dt).max(0.0);
-        if worm.boost_min <= 0.0 {
-            worm.boost_available = false;
-        }
-    } else {
-        // 부스트 OFF
-        worm.mode = SpeedMode::Nomal;
-
-        // 부스트 회복
-        worm.boost_min = (worm.boost_min + worm.boost_recharge * dt).min(worm.boost_max);
-        if worm.boost_min >= worm.boost_max {
-            worm.boost_available = true;
-        }
-    }
+    // 실제 회전과 부스트 게이지는 move_head에서 고정 tick마다 처리한다.
+    worm.boost_input = boost_key;
 }
 
 /// 임시로 만든 리셋 함수
@@ -586,6 +702,7 @@ fn handle_reset(
     mut commands: Commands,
     mut worm: ResMut<Worm>,
     map: Res<Map>,
+    mut actions: MessageWriter<PlayerAction>,
     worm_query: Query<Entity, With<WormShape>>,
 ) {
     if keys.just_pressed(KeyCode::KeyR) {
@@ -596,6 +713,8 @@ fn handle_reset(
         
         // 지렁이 데이터 리셋
         worm.reset(map.radius);
+        // 서버에도 다시 참가해서 서버가 정한 위치로 시작한다
+        actions.write(PlayerAction::Respawned);
         
         // 새로운 지렁이 몸통 생성 (main + caps)
         let path = ShapePath::new().move_to(worm.head).line_to(worm.head + Vec2::new(1.0, 0.0));
@@ -619,43 +738,20 @@ fn handle_reset(
     }
 }
 
-/// 머리를 시간 기반으로 이동시키고, 일정 거리마다 points에 기록
+/// 서버와 같은 주기(FixedUpdate)로 내 지렁이를 미리 움직여본다 (클라이언트 예측).
+/// 서버 상태가 오면 network_plugin에서 되감은 뒤 아직 반영 안 된 입력으로 다시 simulate 한다.
 fn move_head(time: Res<Time>, mut worm: ResMut<Worm>) {
     if worm.is_dead {
         return;
     }
 
-    let dt = time.delta_secs();
-
-    // 속도는 부스트 모드로 설정
-    let speed = match worm.mode {
-        SpeedMode::Nomal => worm.base_speed,
-        SpeedMode::Boost => worm.boost_speed,
-    };
-
-    // Dir2는 길이가 1인 "방향"이므로, as_vec2()로 Vec2를 꺼내서 위치 계산에 사용
-    let new_head = worm.head + worm.dir.as_vec2() * speed * dt;
-
-    // 샘플링: 너무 촘촘하면 점이 과도하게 늘어서 지렁이가 “굵은 덩어리”처럼 보일 수 있음
-    let push = match worm.points.back().copied() {
-        Some(last) => new_head.distance(last) >= worm.sample_distance,
-        None => true,
-    };
-
-    worm.head = new_head;
-
-    if push {
-        worm.points.push_back(new_head);
-        while worm.points.len() > worm.max_points {
-            worm.points.pop_front();
-        }
-    }
+    worm.simulate(time.delta_secs());
 }
 
 /// points로 지렁이 몸통을 다시 그리고 Shape를 교체
 fn redraw_worm(
     worm: Res<Worm>,
-    mut main_shape_q: Query<&mut Shape, (With<WormShape>, Without<WormCap>)>,
+    mut main_shape_q: Query<(&mut Shape, &mut Transform), (With<WormShape>, Without<WormCap>)>,
     mut cap_q: Query<(&mut Shape, &mut Transform, &WormCap), With<WormShape>>,
 ) {
     // points가 안 바뀌었으면 스킵
@@ -669,23 +765,20 @@ fn redraw_worm(
     }
 
     // 1) 일단 polyline (line_to)로 몸통 생성
-    //    여기서 Catmull-Rom 샘플링을 넣으면 “진짜 스플라인” 느낌이 됩니다.
-    let mut path = ShapePath::new().move_to(pts[0]);
-    for p in pts.iter().skip(1) {
-        path = path.line_to(*p);
-    }
+    let path = worm_path(&pts);
 
-    // thickness scales with length; doubled baseline and growth
-    let thickness = (16.0 + worm.points.len() as f32 * 0.24).clamp(16.0, 72.0);
+    let thickness = worm_thickness(worm.points.len());
 
     // 2) Shape 교체로 렌더 반영 (main body)
-    if let Some(mut shape) = main_shape_q.iter_mut().next() {
+    //    서버 보정으로 생긴 오차(correction)는 Transform으로만 더해서 그린다.
+    if let Some((mut shape, mut tf)) = main_shape_q.iter_mut().next() {
         *shape = ShapeBuilder::with(&path).stroke((GREEN, thickness)).build();
+        tf.translation = worm.correction.extend(0.5);
     }
 
     // update caps: head & tail as filled circles
-    let head_pos = *pts.last().unwrap();
-    let tail_pos = pts.first().copied().unwrap_or(head_pos);
+    let head_pos = *pts.last().unwrap() + worm.correction;
+    let tail_pos = pts.first().copied().unwrap_or(head_pos) + worm.correction;
     let cap_radius = thickness * 0.5;
 
     for (mut shape, mut tf, cap) in cap_q.iter_mut() {
@@ -699,6 +792,92 @@ fn redraw_worm(
     }
 }
 
+// thickness scales with length; doubled baseline and growth
+fn worm_thickness(len: usize) -> f32 {
+    (16.0 + len as f32 * 0.24).clamp(16.0, 72.0)
+}
+
+// 여기서 Catmull-Rom 샘플링을 넣으면 “진짜 스플라인” 느낌이 됩니다.
+fn worm_path(pts: &[Vec2]) -> ShapePath {
+    let mut path = ShapePath::new().move_to(pts[0]);
+    for p in pts.iter().skip(1) {
+        path = path.line_to(*p);
+    }
+    path
+}
+
+// 다른 플레이어의 지렁이 (몸통 + 머리/꼬리 cap). 내 지렁이와 달리 WormShape를 달지 않는다.
+#[derive(Component)]
+struct RemoteWormShape {
+    id: u64,
+}
+
+/// RemoteWorms 기준으로 다른 지렁이들을 생성/갱신/삭제한다.
+/// 두께는 내 지렁이(redraw_worm)와 같은 공식을 쓰고, 색상은 서버에서 받은 색을 쓴다.
+fn redraw_remote_worms(
+    mut commands: Commands,
+    remote: Res<RemoteWorms>,
+    mut shape_q: Query<(Entity, &RemoteWormShape, &mut Shape, &mut Transform, Option<&WormCap>)>,
+) {
+    if !remote.is_changed() {
+        return;
+    }
+
+    let mut drawn = HashSet::new();
+
+    for (entity, remote_shape, mut shape, mut tf, cap) in shape_q.iter_mut() {
+        // 나갔거나 죽은 지렁이는 엔티티 삭제
+        let Some(rw) = remote.worms.iter().find(|w| w.id == remote_shape.id) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+        drawn.insert(rw.id);
+
+        if rw.points.len() < 2 {
+            continue;
+        }
+
+        let thickness = worm_thickness(rw.points.len());
+        match cap {
+            None => *shape = ShapeBuilder::with(&worm_path(&rw.points)).stroke((rw.color, thickness)).build(),
+            Some(cap) => {
+                let circle = shapes::Circle { radius: thickness * 0.5, center: Vec2::ZERO };
+                *shape = ShapeBuilder::with(&circle).fill(rw.color).build();
+                let pos = if cap.is_head { rw.points[rw.points.len() - 1] } else { rw.points[0] };
+                // 내 지렁이가 항상 위에 보이도록 살짝 아래에 그린다
+                tf.translation = pos.extend(0.4);
+            },
+        }
+    }
+
+    // 처음 보는 지렁이는 몸통 + cap 엔티티를 새로 생성
+    for rw in remote.worms.iter() {
+        if drawn.contains(&rw.id) || rw.points.len() < 2 {
+            continue;
+        }
+
+        let thickness = worm_thickness(rw.points.len());
+        let cap_circle = shapes::Circle { radius: thickness * 0.5, center: Vec2::ZERO };
+        commands.spawn((
+            ShapeBuilder::with(&worm_path(&rw.points)).stroke((rw.color, thickness)).build(),
+            Transform::from_translation(Vec3::new(0.0, 0.0, 0.4)),
+            RemoteWormShape { id: rw.id },
+        ));
+        commands.spawn((
+            ShapeBuilder::with(&cap_circle).fill(rw.color).build(),
+            Transform::from_translation(rw.points[rw.points.len() - 1].extend(0.4)),
+            RemoteWormShape { id: rw.id },
+            WormCap { is_head: true },
+        ));
+        commands.spawn((
+            ShapeBuilder::with(&cap_circle).fill(rw.color).build(),
+            Transform::from_translation(rw.points[0].extend(0.4)),
+            RemoteWormShape { id: rw.id },
+            WormCap { is_head: false },
+        ));
+    }
+}
+
 fn check_damage_zone(
     time: Res<Time>,
     mut worm: ResMut<Worm>,
@@ -719,22 +898,20 @@ fn check_damage_zone(
 }
 
 fn check_collision(
-    mut commands: Commands,
     mut worm: ResMut<Worm>,
     mut dots: ResMut<Dots>,
     map: Res<Map>,
-    worm_query: Query<Entity, With<WormShape>>,
     dot_query: Query<&Dot>,
     dot_tf_q: Query<&Transform, With<DotsShape>>,
     mut absorbing: ResMut<AbsorbingDots>,
 ) {
+    // 서버도 맵 밖으로 나간 지렁이를 죽이고 ResDie를 보내므로 따로 알리지 않는다.
     if worm.is_outside(&map) {
-        worm.kill(&mut commands, &worm_query);
         worm.is_dead = true;
         return;
     }
 
-    let thickness = (16.0 + worm.points.len() as f32 * 0.24).clamp(16.0, 72.0);
+    let thickness = worm_thickness(worm.points.len());
     // sector center: a bit in front of the head (half thickness)
     let sector_center = worm.head + worm.dir.as_vec2() * (thickness * 0.5);
     // radius: ~150% of thickness
@@ -746,10 +923,10 @@ fn check_collision(
 
     if !removed_entities.is_empty() {
         for entity in removed_entities.into_iter() {
-            let growth = dot_query.get(entity).map(|d| d.growth).unwrap_or(1);
+            let (growth, id) = dot_query.get(entity).map(|d| (d.growth, d.id)).unwrap_or((1, None));
             let start_pos = dot_tf_q.get(entity).map(|t| t.translation.truncate()).unwrap_or(Vec2::ZERO);
             let duration = 0.18;
-            absorbing.items.push((entity, growth, 0.0, duration, start_pos));
+            absorbing.items.push((entity, growth, id, 0.0, duration, start_pos));
         }
     }
 }
@@ -762,6 +939,7 @@ fn animate_absorbing(
     mut transforms: Query<&mut Transform, With<DotsShape>>,
     mut dots: ResMut<Dots>,
     map: Res<Map>,
+    mut actions: MessageWriter<PlayerAction>,
 ) {
     let dt = time.delta_secs();
 
@@ -771,7 +949,7 @@ fn animate_absorbing(
 
     let mut remaining = Vec::new();
 
-    for (entity, growth, mut elapsed, duration, start_pos) in absorbing.items.drain(..) {
+    for (entity, growth, id, mut elapsed, duration, start_pos) in absorbing.items.drain(..) {
         elapsed += dt;
         let t = (elapsed / duration).clamp(0.0, 1.0);
         let target = worm.head;
@@ -788,10 +966,18 @@ fn animate_absorbing(
             // finalize: despawn entity, add growth, spawn replacement
             commands.entity(entity).despawn();
             worm.grow(growth);
-            // spawn replacement
-            dots.spawn(&mut commands, map.radius);
+            match id {
+                // 서버 먹이는 서버가 확인한 뒤 다시 뿌려준다.
+                Some(food_id) => {
+                    actions.write(PlayerAction::Ate { food_id });
+                },
+                // 접속 전에 혼자 만든 점은 직접 다시 뿌린다.
+                None => {
+                    dots.spawn(&mut commands, map.radius);
+                },
+            }
         } else {
-            remaining.push((entity, growth, elapsed, duration, start_pos));
+            remaining.push((entity, growth, id, elapsed, duration, start_pos));
         }
     }
 
@@ -807,12 +993,42 @@ impl RemoteWorms {
     fn new() -> Self {
         Self { worms: Vec::new() }
     }
+
+    // 서버에서 받은 몸통을 스냅샷으로 쌓고, 처음 보는 지렁이면 새로 추가
+    // 실제로 그려지는 points는 InterpolationPlugin이 매 프레임 보간해서 채운다.
+    fn upsert(&mut self, id: u64, color: Color, time: f64, points: Vec<Vec2>) {
+        match self.worms.iter_mut().find(|w| w.id == id) {
+            Some(worm) => {
+                worm.color = color;
+                worm.snapshots.push(time, points);
+            },
+            None => {
+                let mut snapshots = SnapshotBuffer::default();
+                snapshots.push(time, points.clone());
+                self.worms.push(RemoteWorm { id, color, points, snapshots });
+            },
+        }
+    }
+
+    fn remove(&mut self, id: u64) {
+        self.worms.retain(|w| w.id != id);
+    }
+
+    // 서버 스냅샷의 지렁이들로 전부 바꾼다
+    fn reset(&mut self, time: f64, worms: impl IntoIterator<Item = (u64, Color, Vec<Vec2>)>) {
+        self.worms.clear();
+        for (id, color, points) in worms {
+            self.upsert(id, color, time, points);
+        }
+    }
 }
 
 // 서버에서 받게 될 "다른 지렁이"의 상태(최소 정보만)
 struct RemoteWorm { 
     id: u64,
-    points: Vec<Vec2>, 
+    color: Color,
+    points: Vec<Vec2>,          // 지금 화면에 그려지는 (보간된) 몸통
+    snapshots: SnapshotBuffer,  // 서버에서 받은 몸통들
 }
 
 fn check_player_death( 
@@ -821,10 +1037,10 @@ fn check_player_death(
     mut dots: ResMut<Dots>,
     _map: Res<Map>,
     remote: Res<RemoteWorms>,
-    worm_query: Query<Entity, With<WormShape>>,
 ) {
+    // 서버에 연결돼 있으면 충돌은 서버가 판정해서 ResDie로 알려준다.
     // 더미 없이 진행: 현재 원격 지렁이가 없으면 아무 일도 안 함.
-    if remote.worms.is_empty() {
+    if worm.id != 0 || worm.is_dead || remote.worms.is_empty() {
         return;
     }
 
@@ -856,6 +1072,8 @@ fn check_player_death(
         return;
     }
 
+    worm.is_dead = true;
+
     // 여기부터: "내 지렁이를 점으로 변환"
     // 너무 많은 점이 한 번에 생기면 화면이 지저분하니, 몸통 점을 몇 칸씩 건너뛰며 생성
     const STEP: usize = 5;
@@ -865,10 +1083,17 @@ fn check_player_death(
             dots.spawn_at(&mut commands, pos);
         }
     }
+}
 
-    // 죽었으니 내 지렁이 리셋(새 ID로 다시 시작)
-    worm.kill(&mut commands, &worm_query); // Need to despawn body
-    worm.is_dead = true;
+// 어떤 이유로 죽었든(충돌, 맵 밖, 서버의 ResDie) 남아 있는 몸통을 지운다.
+fn despawn_dead_worm(
+    mut commands: Commands,
+    worm: Res<Worm>,
+    worm_query: Query<Entity, With<WormShape>>,
+) {
+    if worm.is_dead {
+        worm.kill(&mut commands, &worm_query);
+    }
 }
 
 #[derive(Debug, Clone)]
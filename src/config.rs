@@ -0,0 +1,350 @@
+use crate::room;
+use bug::game::world::{DamageZoneSettings, WorldSettings};
+use bug::game::TICK_RATE;
+use bug::network::heartbeat;
+use clap::Parser;
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+use tracing_subscriber::filter::Targets;
+
+const MIN_TICK_RATE: f64 = 1.0;
+const MAX_TICK_RATE: f64 = 240.0;
+const MAX_FOOD_COUNT: usize = 10_000;
+const MAX_DAMAGE_ZONE_COUNT: usize = 64;
+const MAX_DAMAGE_PER_SEC: f32 = 1000.0;
+const MAX_IDLE_TIMEOUT_SECS: f64 = 3600.0;
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: f64 = 5.0;
+const MAX_SHUTDOWN_TIMEOUT_SECS: f64 = 300.0;
+
+// 실행 인자. 준 값은 설정 파일보다 우선한다.
+#[derive(Debug, Parser)]
+#[command(name = "server", about = "지렁이 게임 서버")]
+pub struct Args {
+    /// TOML 설정 파일 경로
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+    /// 접속을 받을 주소 (예: 0.0.0.0:8888)
+    #[arg(long)]
+    pub listen_addr: Option<SocketAddr>,
+    /// 초당 tick 수
+    #[arg(long)]
+    pub tick_rate: Option<f64>,
+    /// 방 하나의 최대 인원
+    #[arg(long)]
+    pub max_players: Option<usize>,
+    /// 아무 메세지도 없으면 연결을 끊을 시간 (초)
+    #[arg(long)]
+    pub idle_timeout_secs: Option<f64>,
+    /// 로그 필터 (예: info, server=debug)
+    #[arg(long)]
+    pub log_filter: Option<String>,
+    /// 종료할 때 연결들이 정리되기를 기다리는 최대 시간 (초)
+    #[arg(long)]
+    pub shutdown_timeout_secs: Option<f64>,
+    /// 종료할 때 클라이언트에게 알려줄 재시작 예정 시간 (초)
+    #[arg(long)]
+    pub restart_eta_secs: Option<u32>,
+    /// 기본 방의 맵 반지름
+    #[arg(long)]
+    pub map_radius: Option<f32>,
+    /// 맵 반지름의 최대값. 클라이언트가 만드는 방에도 적용된다
+    #[arg(long)]
+    pub max_map_radius: Option<f32>,
+    /// 방마다 유지할 먹이 수
+    #[arg(long)]
+    pub food_count: Option<usize>,
+    /// 방마다 만들 데미지 영역 수
+    #[arg(long)]
+    pub damage_zone_count: Option<usize>,
+    /// 데미지 영역 반지름의 최소값
+    #[arg(long)]
+    pub damage_zone_min_radius: Option<f32>,
+    /// 데미지 영역 반지름의 최대값
+    #[arg(long)]
+    pub damage_zone_max_radius: Option<f32>,
+    /// 데미지 영역 안에서 초당 줄어드는 몸 길이
+    #[arg(long)]
+    pub damage_per_sec: Option<f32>,
+}
+
+// 설정 파일 형식. 빠진 값은 기본값을 쓰고, 모르는 키는 오타일 수 있으므로 에러로 본다.
+//
+//  listen_addr = "0.0.0.0:8888"
+//  tick_rate = 30.0
+//  max_players = 100
+//  idle_timeout_secs = 10.0
+//  log_filter = "info"
+//  shutdown_timeout_secs = 5.0
+//  restart_eta_secs = 30          # 없으면 재시작 예정 없음
+//
+//  [world]
+//  map_radius = 2500.0
+//  max_map_radius = 10000.0
+//  food_count = 200
+//
+//  [damage_zones]
+//  count = 1
+//  min_radius = 30.0
+//  max_radius = 100.0
+//  damage_per_sec = 30.0
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub listen_addr: SocketAddr,
+    pub tick_rate: f64,
+    pub max_players: usize,
+    pub idle_timeout_secs: f64,
+    pub log_filter: String,
+    pub shutdown_timeout_secs: f64,
+    pub restart_eta_secs: Option<u32>,
+    pub world: WorldConfig,
+    pub damage_zones: DamageZoneConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WorldConfig {
+    pub map_radius: f32,
+    pub max_map_radius: f32,
+    pub food_count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DamageZoneConfig {
+    pub count: usize,
+    pub min_radius: f32,
+    pub max_radius: f32,
+    pub damage_per_sec: f32,
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Failed to read config file. (path: {path}) {source}")]
+    Read { path: PathBuf, source: std::io::Error },
+    #[error("Failed to parse config file. (path: {path}) {source}")]
+    Parse { path: PathBuf, source: toml::de::Error },
+    #[error("{field} must be between {min} and {max}. ({field}: {value})")]
+    OutOfRange { field: &'static str, value: f64, min: f64, max: f64 },
+    #[error("damage_zones.min_radius must not be greater than damage_zones.max_radius. (min_radius: {min_radius}, max_radius: {max_radius})")]
+    ZoneRadius { min_radius: f32, max_radius: f32 },
+    #[error("Invalid log filter. (log_filter: {filter}) {source}")]
+    LogFilter { filter: String, source: tracing_subscriber::filter::ParseError },
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            listen_addr: SocketAddr::from(([0, 0, 0, 0], 8888)),
+            tick_rate: TICK_RATE,
+            max_players: room::MAX_PLAYERS,
+            idle_timeout_secs: heartbeat::DEFAULT_IDLE_TIMEOUT.as_secs_f64(),
+            log_filter: "info".to_string(),
+            shutdown_timeout_secs: DEFAULT_SHUTDOWN_TIMEOUT_SECS,
+            restart_eta_secs: None,
+            world: WorldConfig::default(),
+            damage_zones: DamageZoneConfig::default(),
+        }
+    }
+}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        let settings = WorldSettings::default();
+        Self { map_radius: settings.map_radius, max_map_radius: room::DEFAULT_MAX_MAP_RADIUS, food_count: settings.food_count }
+    }
+}
+
+impl Default for DamageZoneConfig {
+    fn default() -> Self {
+        let settings = DamageZoneSettings::default();
+        Self {
+            count: settings.count,
+            min_radius: settings.min_radius,
+            max_radius: settings.max_radius,
+            damage_per_sec: settings.damage_per_sec,
+        }
+    }
+}
+
+impl Config {
+    // 설정 파일을 읽고 (없으면 기본값) 실행 인자로 덮어쓴 뒤 검사한다.
+    pub fn load(args: &Args) -> Result<Self, ConfigError> {
+        let mut config = match &args.config {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        config.apply_args(args);
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let text = std::fs::read_to_string(path).map_err(|source| ConfigError::Read { path: path.to_path_buf(), source })?;
+        toml::from_str(&text).map_err(|source| ConfigError::Parse { path: path.to_path_buf(), source })
+    }
+
+    fn apply_args(&mut self, args: &Args) {
+        if let Some(listen_addr) = args.listen_addr {
+            self.listen_addr = listen_addr;
+        }
+        if let Some(tick_rate) = args.tick_rate {
+            self.tick_rate = tick_rate;
+        }
+        if let Some(max_players) = args.max_players {
+            self.max_players = max_players;
+        }
+        if let Some(idle_timeout_secs) = args.idle_timeout_secs {
+            self.idle_timeout_secs = idle_timeout_secs;
+        }
+        if let Some(log_filter) = &args.log_filter {
+            self.log_filter = log_filter.clone();
+        }
+        if let Some(shutdown_timeout_secs) = args.shutdown_timeout_secs {
+            self.shutdown_timeout_secs = shutdown_timeout_secs;
+        }
+        if args.restart_eta_secs.is_some() {
+            self.restart_eta_secs = args.restart_eta_secs;
+        }
+        if let Some(map_radius) = args.map_radius {
+            self.world.map_radius = map_radius;
+        }
+        if let Some(max_map_radius) = args.max_map_radius {
+            self.world.max_map_radius = max_map_radius;
+        }
+        if let Some(food_count) = args.food_count {
+            self.world.food_count = food_count;
+        }
+        if let Some(count) = args.damage_zone_count {
+            self.damage_zones.count = count;
+        }
+        if let Some(min_radius) = args.damage_zone_min_radius {
+            self.damage_zones.min_radius = min_radius;
+        }
+        if let Some(max_radius) = args.damage_zone_max_radius {
+            self.damage_zones.max_radius = max_radius;
+        }
+        if let Some(damage_per_sec) = args.damage_per_sec {
+            self.damage_zones.damage_per_sec = damage_per_sec;
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        check_range("tick_rate", self.tick_rate, MIN_TICK_RATE, MAX_TICK_RATE)?;
+        check_range("max_players", self.max_players as f64, 1.0, room::MAX_PLAYERS as f64)?;
+        check_range("idle_timeout_secs", self.idle_timeout_secs, 1.0, MAX_IDLE_TIMEOUT_SECS)?;
+        self.log_filter()?;
+        check_range("shutdown_timeout_secs", self.shutdown_timeout_secs, 0.0, MAX_SHUTDOWN_TIMEOUT_SECS)?;
+        check_range("world.max_map_radius", self.world.max_map_radius as f64, room::MIN_MAP_RADIUS as f64, f32::MAX as f64)?;
+        check_range("world.map_radius", self.world.map_radius as f64, room::MIN_MAP_RADIUS as f64, self.world.max_map_radius as f64)?;
+        check_range("world.food_count", self.world.food_count as f64, 0.0, MAX_FOOD_COUNT as f64)?;
+        check_range("damage_zones.count", self.damage_zones.count as f64, 0.0, MAX_DAMAGE_ZONE_COUNT as f64)?;
+        check_range("damage_zones.min_radius", self.damage_zones.min_radius as f64, 1.0, self.world.map_radius as f64)?;
+        check_range("damage_zones.max_radius", self.damage_zones.max_radius as f64, 1.0, self.world.map_radius as f64)?;
+        if self.damage_zones.min_radius > self.damage_zones.max_radius {
+            return Err(ConfigError::ZoneRadius { min_radius: self.damage_zones.min_radius, max_radius: self.damage_zones.max_radius });
+        }
+        check_range("damage_zones.damage_per_sec", self.damage_zones.damage_per_sec as f64, 0.0, MAX_DAMAGE_PER_SEC as f64)?;
+        Ok(())
+    }
+
+    pub fn log_filter(&self) -> Result<Targets, ConfigError> {
+        self.log_filter.parse().map_err(|source| ConfigError::LogFilter { filter: self.log_filter.clone(), source })
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        Duration::from_secs_f64(self.idle_timeout_secs)
+    }
+
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs_f64(self.shutdown_timeout_secs)
+    }
+
+    pub fn world_settings(&self) -> WorldSettings {
+        WorldSettings {
+            map_radius: self.world.map_radius,
+            food_count: self.world.food_count,
+            damage_zones: DamageZoneSettings {
+                count: self.damage_zones.count,
+                min_radius: self.damage_zones.min_radius,
+                max_radius: self.damage_zones.max_radius,
+                damage_per_sec: self.damage_zones.damage_per_sec,
+            },
+        }
+    }
+}
+
+// NaN도 범위 밖으로 본다
+fn check_range(field: &'static str, value: f64, min: f64, max: f64) -> Result<(), ConfigError> {
+    if !(min..=max).contains(&value) {
+        return Err(ConfigError::OutOfRange { field, value, min, max });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_range() {
+        assert!(check_range("tick_rate", MIN_TICK_RATE, MIN_TICK_RATE, MAX_TICK_RATE).is_ok());
+        assert!(check_range("tick_rate", MAX_TICK_RATE, MIN_TICK_RATE, MAX_TICK_RATE).is_ok());
+        assert!(matches!(
+            check_range("tick_rate", MIN_TICK_RATE - 0.5, MIN_TICK_RATE, MAX_TICK_RATE),
+            Err(ConfigError::OutOfRange { field: "tick_rate", .. })
+        ));
+        assert!(check_range("tick_rate", MAX_TICK_RATE + 0.5, MIN_TICK_RATE, MAX_TICK_RATE).is_err());
+        assert!(check_range("tick_rate", f64::NAN, MIN_TICK_RATE, MAX_TICK_RATE).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Config::default().validate().is_ok());
+
+        // 맵은 기본 크기보다 크게 만들 수 있고, 설정한 최대값까지만 받는다
+        let mut config = Config::default();
+        config.world.map_radius = 8000.0;
+        assert!(config.validate().is_ok());
+        config.world.max_map_radius = 5000.0;
+        assert!(matches!(config.validate(), Err(ConfigError::OutOfRange { field: "world.map_radius", .. })));
+        config.world.max_map_radius = f32::INFINITY;
+        assert!(matches!(config.validate(), Err(ConfigError::OutOfRange { field: "world.max_map_radius", .. })));
+
+        let mut config = Config::default();
+        config.damage_zones.min_radius = 80.0;
+        config.damage_zones.max_radius = 40.0;
+        assert!(matches!(config.validate(), Err(ConfigError::ZoneRadius { .. })));
+
+        let config = Config { idle_timeout_secs: f64::NAN, ..Config::default() };
+        assert!(matches!(config.validate(), Err(ConfigError::OutOfRange { field: "idle_timeout_secs", .. })));
+    }
+
+    #[test]
+    fn test_parse() {
+        // 빠진 표는 기본값
+        let config = toml::from_str::<Config>("tick_rate = 60.0").unwrap();
+        assert_eq!(config.tick_rate, 60.0);
+        assert_eq!(config.world, WorldConfig::default());
+        assert_eq!(config.damage_zones, DamageZoneConfig::default());
+
+        // 오타는 에러
+        assert!(toml::from_str::<Config>("tick_rat = 60.0").is_err());
+        assert!(toml::from_str::<Config>("[world]\nmap_raduis = 1000.0").is_err());
+    }
+
+    #[test]
+    fn test_args_override_file() {
+        let mut config = toml::from_str::<Config>("tick_rate = 20.0\n[world]\nmap_radius = 1000.0\nfood_count = 50").unwrap();
+        let args = Args::parse_from(["server", "--tick-rate", "60", "--food-count", "300"]);
+        config.apply_args(&args);
+
+        assert_eq!(config.tick_rate, 60.0);
+        assert_eq!(config.world.food_count, 300);
+        // 주지 않은 값은 파일 그대로
+        assert_eq!(config.world.map_radius, 1000.0);
+    }
+}
@@ -0,0 +1,106 @@
+use glam::Vec2;
+use std::collections::HashMap;
+
+// 좌표를 cell_size 크기의 칸으로 나눠 담아두고, 어떤 영역 근처에 있는 항목만 빠르게 찾는다.
+// 항목마다 좌표를 같이 들고 있어서 칸 단위로 추린 뒤에 정확한 범위로 다시 거른다.
+#[derive(Debug, Clone)]
+pub struct SpatialGrid<T> {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<(T, Vec2)>>,
+    len: usize,
+}
+
+impl<T: Copy + PartialEq> SpatialGrid<T> {
+    pub fn new(cell_size: f32) -> Self {
+        Self { cell_size, cells: HashMap::new(), len: 0 }
+    }
+
+    fn cell(&self, position: Vec2) -> (i32, i32) {
+        ((position.x / self.cell_size).floor() as i32, (position.y / self.cell_size).floor() as i32)
+    }
+
+    pub fn insert(&mut self, item: T, position: Vec2) {
+        let cell = self.cell(position);
+        self.cells.entry(cell).or_default().push((item, position));
+        self.len += 1;
+    }
+
+    // 넣을 때와 같은 좌표를 줘야 찾을 수 있다
+    pub fn remove(&mut self, item: T, position: Vec2) -> bool {
+        let cell = self.cell(position);
+        let Some(items) = self.cells.get_mut(&cell) else {
+            return false;
+        };
+        let Some(index) = items.iter().position(|(other, _)| *other == item) else {
+            return false;
+        };
+        items.swap_remove(index);
+        if items.is_empty() {
+            self.cells.remove(&cell);
+        }
+        self.len -= 1;
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.len = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (T, Vec2)> + '_ {
+        self.cells.values().flatten().copied()
+    }
+
+    // min ~ max 사각형 안에 있는 항목들
+    pub fn query_rect(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = (T, Vec2)> + '_ {
+        let (min_x, min_y) = self.cell(min);
+        let (max_x, max_y) = self.cell(max);
+        (min_x..=max_x)
+            .flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .filter(move |(_, position)| position.cmpge(min).all() && position.cmple(max).all())
+    }
+
+    // center에서 radius 안에 있는 항목들
+    pub fn query_circle(&self, center: Vec2, radius: f32) -> impl Iterator<Item = (T, Vec2)> + '_ {
+        self.query_rect(center - Vec2::splat(radius), center + Vec2::splat(radius))
+            .filter(move |(_, position)| position.distance(center) <= radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_and_remove() {
+        let mut grid = SpatialGrid::new(100.0);
+        grid.insert(1, Vec2::new(10.0, 10.0));
+        grid.insert(2, Vec2::new(-150.0, 40.0));
+        grid.insert(3, Vec2::new(90.0, 90.0));
+        assert_eq!(grid.len(), 3);
+
+        let mut found = grid.query_rect(Vec2::new(-200.0, 0.0), Vec2::new(50.0, 50.0)).map(|(id, _)| id).collect::<Vec<_>>();
+        found.sort();
+        assert_eq!(found, vec![1, 2]);
+
+        // 같은 칸에 있어도 원 밖이면 빠진다
+        let found = grid.query_circle(Vec2::ZERO, 20.0).map(|(id, _)| id).collect::<Vec<_>>();
+        assert_eq!(found, vec![1]);
+
+        assert!(grid.remove(1, Vec2::new(10.0, 10.0)));
+        assert!(!grid.remove(1, Vec2::new(10.0, 10.0)));
+        assert_eq!(grid.query_circle(Vec2::ZERO, 20.0).count(), 0);
+        assert_eq!(grid.len(), 2);
+    }
+}
@@ -0,0 +1,93 @@
+use crate::game::worm::WormState;
+use crate::network::message::snapshot::FoodInfo;
+use crate::network::message::worm_body::WormBody;
+use glam::Vec2;
+use std::collections::HashSet;
+
+// 모든 지렁이를 모든 클라이언트에게 보내면 인원 제곱으로 늘어나므로, 각자 화면 근처에 있는 것만 보낸다.
+
+// 클라이언트 기본 창 크기(1280x720)의 절반. 서버는 실제 창 크기를 모르므로 이 크기로 본다.
+pub const VIEW_HALF_EXTENTS: Vec2 = Vec2::new(640.0, 360.0);
+// 화면 밖 조금 더까지 미리 보내서 화면에 들어오는 순간 이미 그려져 있게 한다
+pub const ENTER_MARGIN: f32 = 200.0;
+// 경계에서 들어갔다 나갔다를 반복하지 않도록, 나갈 때는 더 멀어져야 한다
+pub const LEAVE_MARGIN: f32 = 400.0;
+// 시야 계산에 쓰는 격자 한 칸 크기
+pub const CELL_SIZE: f32 = 256.0;
+
+// 클라이언트 camera_follow와 같은 식. 몸이 길수록 카메라가 멀어진다.
+pub fn camera_zoom(body_length: usize) -> f32 {
+    (1.0 + body_length as f32 * 0.005).clamp(1.0, 3.0)
+}
+
+// 클라이언트 화면에 해당하는 사각형
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InterestArea {
+    pub center: Vec2,
+    pub half_extents: Vec2,
+}
+
+impl InterestArea {
+    pub fn around(worm: &WormState) -> Self {
+        Self { center: worm.head, half_extents: VIEW_HALF_EXTENTS * camera_zoom(worm.points.len()) }
+    }
+
+    pub fn expanded(&self, margin: f32) -> Self {
+        Self { center: self.center, half_extents: self.half_extents + Vec2::splat(margin) }
+    }
+
+    pub fn min(&self) -> Vec2 {
+        self.center - self.half_extents
+    }
+
+    pub fn max(&self) -> Vec2 {
+        self.center + self.half_extents
+    }
+
+    pub fn contains(&self, position: Vec2) -> bool {
+        (position - self.center).abs().cmple(self.half_extents).all()
+    }
+}
+
+// 클라이언트 하나가 지금 알고 있는 다른 지렁이와 먹이.
+// 죽은 뒤에도 카메라는 그 자리에 있으므로 마지막 시야를 유지한다.
+#[derive(Debug, Default)]
+pub struct Interest {
+    pub area: Option<InterestArea>,
+    pub worms: HashSet<usize>,
+    pub foods: HashSet<u32>,
+}
+
+// 한 tick 동안 클라이언트 하나의 시야 변화
+#[derive(Debug, Default)]
+pub struct ViewChanges {
+    pub client_id: usize,
+    pub entered_worms: Vec<WormBody>,
+    pub entered_foods: Vec<FoodInfo>,
+    pub left_worms: Vec<usize>,
+    pub left_foods: Vec<u32>,
+    // 알고 있던 먹이 중 먹혀서 없어진 것
+    pub eaten_foods: Vec<u32>,
+    // 시야 안에 이번 tick 새로 생긴 먹이. 시야에 들어온 먹이와 달리 ResFoodSpawn으로 보낸다.
+    pub spawned_foods: Vec<FoodInfo>,
+    // 전부터 보이던 지렁이. 이번 tick의 ResMove를 받는다.
+    pub visible_worms: Vec<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_area_grows_with_body() {
+        let mut worm = WormState::spawn(1, 2500.0);
+        let short = InterestArea::around(&worm);
+        assert_eq!(short.half_extents, VIEW_HALF_EXTENTS * camera_zoom(worm.points.len()));
+
+        worm.points.extend(std::iter::repeat_n(worm.head, 1000));
+        let long = InterestArea::around(&worm);
+        assert_eq!(long.half_extents, VIEW_HALF_EXTENTS * 3.0);
+        assert!(long.contains(worm.head + VIEW_HALF_EXTENTS * 2.0));
+        assert!(!short.contains(worm.head + VIEW_HALF_EXTENTS * 2.0));
+    }
+}
@@ -0,0 +1,10 @@
+// 서버가 권위를 갖고 돌리는 게임 규칙.
+// 클라이언트는 입력만 보내고, 서버가 일정한 주기(tick)로 지렁이를 움직인 결과를 받아서 그린다.
+pub mod worm;
+pub mod world;
+pub mod validation;
+pub mod grid;
+pub mod interest;
+
+// 서버 시뮬레이션 주기 (초당 tick 수)
+pub const TICK_RATE: f64 = 30.0;
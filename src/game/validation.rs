@@ -0,0 +1,218 @@
+use crate::game::worm::WormState;
+use glam::Vec2;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+// ReqMove로 몸통을 직접 보고하는 클라이언트를 믿지 않고, 이전 상태에서 도달할 수 있는 몸통인지 확인한다.
+
+// 지연과 프레임 간격 때문에 조금 더 움직인 것으로 보이는 만큼 봐주는 시간
+pub const SPEED_SLACK: Duration = Duration::from_millis(250);
+// 보고 사이가 이보다 길어도 이만큼만 움직일 수 있다고 본다. 오래 조용하다가 순간이동하는 것을 막는다.
+pub const MAX_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+// 몸통 점 사이 간격은 SAMPLE_DISTANCE 이상이고, 느린 프레임에서 부스트하면 몇 배까지 벌어질 수 있다.
+pub const MAX_SPACING: f32 = WormState::SAMPLE_DISTANCE * 4.0;
+// 맵 밖으로 나간 머리는 다음 tick에 죽으므로 경계 근처까지는 받아준다.
+pub const MAP_SLACK: f32 = WormState::BOOST_SPEED * 0.25;
+// step은 tick마다 남은 각도의 TURN_SPEED * dt 만큼 도는데, 남은 각도는 반바퀴를 넘지 않는다.
+pub const MAX_TURN_RATE: f32 = WormState::TURN_SPEED * std::f32::consts::PI;
+// 회전은 SPEED_SLACK 만큼 봐주면 한 번에 반바퀴도 돌 수 있으므로 따로 조금만 봐준다.
+pub const TURN_SLACK: Duration = Duration::from_millis(50);
+
+// 이 시간 안에 위반이 KICK_THRESHOLD 번 쌓이면 연결을 끊는다
+pub const VIOLATION_WINDOW: Duration = Duration::from_secs(10);
+pub const KICK_THRESHOLD: u32 = 20;
+
+// 보고된 몸통이 말이 안 되는 이유
+#[derive(Error, Debug, Clone, Copy, PartialEq)]
+pub enum MoveViolation {
+    #[error("Body has no points.")]
+    Empty,
+
+    // 잘라서 반영하고 위반으로만 센다
+    #[error("Body is longer than earned. (length: {length}, max length: {max_length})")]
+    TooLong { length: usize, max_length: usize },
+
+    #[error("Point is outside the map. (distance: {distance}, map radius: {map_radius})")]
+    OutsideMap { distance: f32, map_radius: f32 },
+
+    #[error("Points are too far apart. (spacing: {spacing}, max spacing: {max_spacing})")]
+    BadSpacing { spacing: f32, max_spacing: f32 },
+
+    #[error("Head moved too fast. (distance: {distance}, max distance: {max_distance})")]
+    TooFast { distance: f32, max_distance: f32 },
+
+    #[error("Head turned too sharply. (angle: {angle}, max angle: {max_angle})")]
+    TooSharpTurn { angle: f32, max_angle: f32 },
+}
+
+impl MoveViolation {
+    // 몸통을 버리지 않고 고쳐서 반영한 경우
+    pub fn is_clamped(&self) -> bool {
+        matches!(self, MoveViolation::TooLong { .. })
+    }
+}
+
+// 마지막으로 받아준 상태에서 elapsed 동안 points 몸통이 될 수 있는지.
+// 길이는 다른 검사를 모두 통과한 뒤에 보므로, TooLong이면 잘라서 반영해도 된다.
+pub fn check_move(worm: &WormState, points: &[Vec2], map_radius: f32, elapsed: Duration) -> Result<(), MoveViolation> {
+    let Some(&head) = points.last() else {
+        return Err(MoveViolation::Empty);
+    };
+
+    let max_radius = map_radius + MAP_SLACK;
+    if let Some(distance) = points.iter().map(|point| point.length()).find(|&distance| distance > max_radius) {
+        return Err(MoveViolation::OutsideMap { distance, map_radius });
+    }
+
+    if let Some(spacing) = points.windows(2).map(|pair| pair[0].distance(pair[1])).find(|&spacing| spacing > MAX_SPACING) {
+        return Err(MoveViolation::BadSpacing { spacing, max_spacing: MAX_SPACING });
+    }
+
+    let elapsed = elapsed.min(MAX_REPORT_INTERVAL);
+
+    // 부스트 속도는 게이지가 남아있는 동안만 낼 수 있다
+    let moving_secs = (elapsed + SPEED_SLACK).as_secs_f32();
+    let boost_secs = if worm.boost_available { worm.boost_remaining.clamp(0.0, moving_secs) } else { 0.0 };
+    let max_distance = WormState::BOOST_SPEED * boost_secs + WormState::BASE_SPEED * (moving_secs - boost_secs);
+    let distance = worm.head.distance(head);
+    if distance > max_distance {
+        return Err(MoveViolation::TooFast { distance, max_distance });
+    }
+
+    if let Some(dir) = reported_dir(worm, points) {
+        let max_angle = (MAX_TURN_RATE * (elapsed + TURN_SLACK).as_secs_f32()).min(std::f32::consts::PI);
+        let angle = worm.dir.angle_to(dir).abs();
+        if angle > max_angle {
+            return Err(MoveViolation::TooSharpTurn { angle, max_angle });
+        }
+    }
+
+    if points.len() > worm.max_points {
+        return Err(MoveViolation::TooLong { length: points.len(), max_length: worm.max_points });
+    }
+
+    Ok(())
+}
+
+// 보고된 몸통의 머리 방향. 마지막 마디가 없으면 이전 머리에서 새 머리로 간 방향을 쓰고, 움직이지 않았으면 None.
+pub fn reported_dir(worm: &WormState, points: &[Vec2]) -> Option<Vec2> {
+    let head = *points.last()?;
+    let from = match points.len() {
+        1 => worm.head,
+        len => points[len - 2],
+    };
+    (head - from).try_normalize()
+}
+
+// 연결마다 하나씩 두고 위반 횟수를 센다. 가끔 튀는 값은 창이 지나면 잊는다.
+#[derive(Debug)]
+pub struct ViolationCounter {
+    count: u32,
+    window_started: Instant,
+}
+
+impl ViolationCounter {
+    pub fn new() -> Self {
+        Self { count: 0, window_started: Instant::now() }
+    }
+
+    pub fn record(&mut self, now: Instant) {
+        if now.duration_since(self.window_started) > VIOLATION_WINDOW {
+            self.count = 0;
+            self.window_started = now;
+        }
+        self.count += 1;
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    // 끊어야 할 만큼 쌓였는지
+    pub fn is_exceeded(&self) -> bool {
+        self.count >= KICK_THRESHOLD
+    }
+}
+
+impl Default for ViolationCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn straight_body(worm: &WormState, length: usize, spacing: f32) -> Vec<Vec2> {
+        (0..length).rev().map(|i| worm.head - worm.dir * spacing * i as f32).collect()
+    }
+
+    #[test]
+    fn test_check_move() {
+        let worm = WormState::spawn(1, 2500.0);
+        let elapsed = Duration::from_millis(100);
+
+        assert_eq!(check_move(&worm, &straight_body(&worm, 10, WormState::SAMPLE_DISTANCE), 2500.0, elapsed), Ok(()));
+        assert_eq!(check_move(&worm, &[], 2500.0, elapsed), Err(MoveViolation::Empty));
+        assert!(matches!(
+            check_move(&worm, &straight_body(&worm, 3, 50.0), 2500.0, elapsed),
+            Err(MoveViolation::BadSpacing { .. })
+        ));
+        assert!(matches!(
+            check_move(&worm, &[worm.head + worm.dir * 500.0], 2500.0, elapsed),
+            Err(MoveViolation::TooFast { .. })
+        ));
+        assert!(matches!(
+            check_move(&worm, &[Vec2::new(5000.0, 0.0)], 2500.0, elapsed),
+            Err(MoveViolation::OutsideMap { .. })
+        ));
+        let too_long = check_move(&worm, &straight_body(&worm, worm.max_points + 1, WormState::SAMPLE_DISTANCE), 2500.0, elapsed);
+        assert!(too_long.is_err_and(|violation| violation.is_clamped()));
+    }
+
+    #[test]
+    fn test_boost_needs_gauge() {
+        let mut worm = WormState::spawn(1, 2500.0);
+        let elapsed = Duration::from_millis(500);
+        let boosted = [worm.head + worm.dir * WormState::BOOST_SPEED * 0.7];
+
+        assert_eq!(check_move(&worm, &boosted, 2500.0, elapsed), Ok(()));
+
+        worm.boost_remaining = 0.0;
+        worm.boost_available = false;
+        assert!(matches!(check_move(&worm, &boosted, 2500.0, elapsed), Err(MoveViolation::TooFast { .. })));
+    }
+
+    #[test]
+    fn test_sharp_turn() {
+        let worm = WormState::spawn(1, 2500.0);
+        let elapsed = Duration::from_millis(100);
+
+        // 반바퀴 돌아서 왔던 쪽으로 간 경우
+        let turned = [worm.head, worm.head - worm.dir * WormState::SAMPLE_DISTANCE];
+        assert!(matches!(check_move(&worm, &turned, 2500.0, elapsed), Err(MoveViolation::TooSharpTurn { .. })));
+
+        // tick 하나 만큼 돈 것은 받아준다
+        let angle = MAX_TURN_RATE * 0.1;
+        let dir = Vec2::from_angle(angle).rotate(worm.dir);
+        let curved = [worm.head, worm.head + dir * WormState::SAMPLE_DISTANCE];
+        assert_eq!(check_move(&worm, &curved, 2500.0, elapsed), Ok(()));
+    }
+
+    #[test]
+    fn test_violation_window() {
+        let mut counter = ViolationCounter::new();
+        let now = Instant::now();
+
+        for _ in 0..KICK_THRESHOLD {
+            counter.record(now);
+        }
+        assert!(counter.is_exceeded());
+
+        // 창이 지나면 처음부터 다시 센다
+        counter.record(now + VIOLATION_WINDOW * 2);
+        assert_eq!(counter.count(), 1);
+        assert!(!counter.is_exceeded());
+    }
+}
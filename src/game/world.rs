@@ -0,0 +1,563 @@
+use crate::game::grid::SpatialGrid;
+use crate::game::interest::{self, Interest, InterestArea, ViewChanges};
+use crate::game::validation::{self, MoveViolation};
+use crate::game::worm::WormState;
+use crate::network::message::snapshot::{FoodInfo, ZoneInfo};
+use crate::network::message::worm_body::WormBody;
+use glam::Vec2;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+// 머리가 들어가 있는 동안 초당 damage_per_sec 만큼 몸 길이가 줄어드는 영역
+#[derive(Debug, Clone)]
+pub struct DamageZone {
+    pub center: Vec2,
+    pub radius: f32,
+    pub damage_per_sec: f32,
+}
+
+impl DamageZone {
+    pub const MIN_RADIUS: f32 = 30.0;
+    pub const MAX_RADIUS: f32 = 100.0;
+    pub const DAMAGE_PER_SEC: f32 = 30.0;
+
+    // 맵 안에 완전히 들어가는 임의의 원
+    pub fn random(map_radius: f32, settings: &DamageZoneSettings) -> Self {
+        let mut rng = rand::rng();
+
+        let max_radius = settings.max_radius.min(map_radius);
+        let radius = rng.random_range(settings.min_radius.min(max_radius)..=max_radius);
+
+        let max_distance = map_radius - radius;
+        let center = if max_distance <= 0.0 {
+            Vec2::ZERO
+        } else {
+            let r = rng.random_range(0.0..1.0f32).sqrt() * max_distance;
+            let theta = rng.random_range(0.0..std::f32::consts::TAU);
+            Vec2::new(r * theta.cos(), r * theta.sin())
+        };
+
+        Self { center, radius, damage_per_sec: settings.damage_per_sec }
+    }
+
+    pub fn to_zone_info(&self) -> ZoneInfo {
+        ZoneInfo { center: (self.center.x, self.center.y), radius: self.radius, damage_per_sec: self.damage_per_sec }
+    }
+}
+
+// 월드를 만들 때 뿌리는 데미지 영역들
+#[derive(Debug, Clone, PartialEq)]
+pub struct DamageZoneSettings {
+    pub count: usize,
+    pub min_radius: f32,
+    pub max_radius: f32,
+    pub damage_per_sec: f32,
+}
+
+impl Default for DamageZoneSettings {
+    fn default() -> Self {
+        Self {
+            count: 1,
+            min_radius: DamageZone::MIN_RADIUS,
+            max_radius: DamageZone::MAX_RADIUS,
+            damage_per_sec: DamageZone::DAMAGE_PER_SEC,
+        }
+    }
+}
+
+// 월드마다 다르게 정할 수 있는 값들. 서버는 설정 파일과 실행 인자로 채운다.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldSettings {
+    pub map_radius: f32,
+    pub food_count: usize,      // 먹혀서 줄어들면 이 수까지 다시 채운다
+    pub damage_zones: DamageZoneSettings,
+}
+
+impl Default for WorldSettings {
+    fn default() -> Self {
+        Self {
+            map_radius: World::MAP_RADIUS,
+            food_count: World::FOOD_COUNT,
+            damage_zones: DamageZoneSettings::default(),
+        }
+    }
+}
+
+// 맵에 뿌려진 먹이. id는 서버가 정하고, 클라이언트는 먹은 먹이를 id로 알려준다.
+#[derive(Debug, Clone)]
+pub struct Food {
+    pub id: u32,
+    pub position: Vec2,
+    pub growth: usize,
+}
+
+impl Food {
+    pub const MIN_GROWTH: usize = 1;
+    pub const MAX_GROWTH: usize = 3;
+
+    // 맵 안쪽 절반과 바깥쪽 절반에 반반씩 뿌린다
+    pub fn random_position(map_radius: f32) -> Vec2 {
+        let mut rng = rand::rng();
+
+        let half_radius = map_radius * 0.5;
+        let r = if rng.random_bool(0.5) {
+            rng.random_range(0.0..half_radius)
+        } else {
+            rng.random_range(half_radius..map_radius)
+        };
+        let theta = rng.random_range(0.0..std::f32::consts::TAU);
+        Vec2::new(r * theta.cos(), r * theta.sin())
+    }
+
+    pub fn random_growth() -> usize {
+        rand::rng().random_range(Self::MIN_GROWTH..=Self::MAX_GROWTH)
+    }
+
+    pub fn to_food_info(&self) -> FoodInfo {
+        FoodInfo { id: self.id, position: (self.position.x, self.position.y), growth: self.growth }
+    }
+}
+
+// step()에서 죽은 지렁이. 다른 지렁이 몸통에 머리를 부딪혔으면 그 몸통의 주인이 killer다.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Death {
+    pub client_id: usize,
+    pub killer_id: Option<usize>,
+}
+
+// 서버 한 대가 돌리는 게임 월드. 연결 태스크와 tick 루프가 Mutex로 공유한다.
+#[derive(Debug)]
+pub struct World {
+    pub map_radius: f32,
+    // 새로 참가한 클라이언트는 ResSnapshot으로 받아서 똑같이 그린다.
+    pub damage_zones: Vec<DamageZone>,
+    worms: HashMap<usize, WormState>,
+
+    // 먹이는 서버만 만들고 지운다.
+    food_count: usize,
+    foods: HashMap<u32, Food>,
+    food_grid: SpatialGrid<u32>,
+    next_food_id: u32,
+    // 마지막으로 시야를 계산한 뒤에 새로 생긴 먹이
+    spawned_foods: HashSet<u32>,
+
+    // 참가한 클라이언트마다 지금 보내주고 있는 지렁이와 먹이
+    interests: HashMap<usize, Interest>,
+}
+
+impl World {
+    // 기본 맵 반지름. 방마다 다르게 만들 수 있다.
+    pub const MAP_RADIUS: f32 = 2500.0;
+    pub const FOOD_COUNT: usize = 200;
+    // 클라이언트는 머리 앞 부채꼴 안의 먹이를 빨아들인 뒤에 ReqEat을 보내므로,
+    // 몸 두께로 정해지는 부채꼴 크기에 빨아들이는 동안 움직인 거리와 지연만큼 여유를 더 준다.
+    pub const EAT_TOLERANCE: f32 = 120.0;
+    // 죽은 지렁이의 몸통 점 몇 개마다 먹이를 하나 만들지
+    pub const BODY_FOOD_STEP: usize = 5;
+    // 다른 지렁이와 겹치지 않는 자리를 이만큼 뽑아보고, 그래도 겹치면 마지막 자리에 생성한다
+    pub const SPAWN_ATTEMPTS: usize = 10;
+
+    pub fn new(settings: &WorldSettings) -> Self {
+        let map_radius = settings.map_radius;
+        let mut world = Self {
+            map_radius,
+            damage_zones: (0..settings.damage_zones.count).map(|_| DamageZone::random(map_radius, &settings.damage_zones)).collect(),
+            worms: HashMap::new(),
+            food_count: settings.food_count,
+            foods: HashMap::new(),
+            food_grid: SpatialGrid::new(interest::CELL_SIZE),
+            next_food_id: 0,
+            spawned_foods: HashSet::new(),
+            interests: HashMap::new(),
+        };
+        world.replenish_food();
+        world
+    }
+
+    // 새로 참가하거나, 죽은 뒤 다시 참가하는 지렁이를 생성.
+    // 참가하면 스냅샷을 새로 받으므로 시야도 처음부터 다시 계산한다.
+    // 다른 지렁이 몸통 위에 생기면 다음 tick에 바로 죽고 상대가 킬을 얻으므로 겹치지 않는 자리를 다시 뽑는다.
+    pub fn spawn(&mut self, client_id: usize) -> &WormState {
+        let mut worm = WormState::spawn(client_id, self.map_radius);
+        for _ in 1..Self::SPAWN_ATTEMPTS {
+            if !self.overlaps_other(&worm) {
+                break;
+            }
+            worm = WormState::spawn(client_id, self.map_radius);
+        }
+        self.worms.insert(client_id, worm);
+        self.interests.insert(client_id, Interest::default());
+        &self.worms[&client_id]
+    }
+
+    // 죽었거나 없는 지렁이만 새 위치에 다시 만든다. 살아있으면 순간이동이 되므로 None.
+    pub fn respawn(&mut self, client_id: usize) -> Option<&WormState> {
+        if self.worms.get(&client_id).is_some_and(|worm| !worm.is_dead) {
+            return None;
+        }
+        Some(self.spawn(client_id))
+    }
+
+    pub fn remove(&mut self, client_id: usize) -> Option<WormState> {
+        self.interests.remove(&client_id);
+        self.forget_worm(client_id);
+        self.worms.remove(&client_id)
+    }
+
+    pub fn worm(&self, client_id: usize) -> Option<&WormState> {
+        self.worms.get(&client_id)
+    }
+
+    pub fn worm_mut(&mut self, client_id: usize) -> Option<&mut WormState> {
+        self.worms.get_mut(&client_id)
+    }
+
+    pub fn apply_input(&mut self, client_id: usize, seq: u32, target_dir: Vec2, boost: bool) -> bool {
+        match self.worms.get_mut(&client_id) {
+            Some(worm) => {
+                worm.client_reported = false;
+                worm.apply_input(seq, target_dir, boost);
+                true
+            },
+            None => false,
+        }
+    }
+
+    // 몸통을 직접 보고하는 클라이언트 (ReqMove). 모르는 지렁이면 None.
+    // 말이 안 되는 몸통은 버리고, 길이만 넘친 몸통은 머리 쪽만 남겨서 반영한다.
+    pub fn report_body(&mut self, worm_body: &WormBody, now: Instant) -> Option<Result<(), MoveViolation>> {
+        let worm = self.worms.get_mut(&worm_body.client_id()).filter(|worm| !worm.is_dead)?;
+        let mut points = worm_body.positions().iter().map(|&(x, y)| Vec2::new(x, y)).collect::<Vec<_>>();
+
+        let elapsed = now.duration_since(worm.reported_at);
+        let result = validation::check_move(worm, &points, self.map_radius, elapsed);
+        match result {
+            Err(violation) if !violation.is_clamped() => return Some(result),
+            Err(_) => {
+                points.drain(..points.len() - worm.max_points);
+            },
+            Ok(()) => {},
+        }
+
+        // 다음 보고의 속도와 회전은 이번에 받아준 머리와 방향을 기준으로 본다
+        if let Some(&head) = points.last() {
+            worm.spend_reported_boost(worm.head.distance(head), elapsed.as_secs_f32());
+            if let Some(dir) = validation::reported_dir(worm, &points) {
+                worm.dir = dir;
+            }
+            worm.head = head;
+        }
+        worm.client_reported = true;
+        worm.reported_at = now;
+        worm.points = points.into();
+        Some(result)
+    }
+
+    pub fn add_food(&mut self, position: Vec2, growth: usize) -> u32 {
+        let id = self.next_food_id;
+        self.next_food_id = self.next_food_id.wrapping_add(1);
+        self.foods.insert(id, Food { id, position, growth });
+        self.food_grid.insert(id, position);
+        self.spawned_foods.insert(id);
+        id
+    }
+
+    // 먹힌 만큼 임의의 위치에 다시 뿌린다
+    pub fn replenish_food(&mut self) {
+        while self.foods.len() < self.food_count {
+            self.add_food(Food::random_position(self.map_radius), Food::random_growth());
+        }
+    }
+
+    // 먹이가 실제로 있고 머리 근처에 있을 때만 먹게 해준다. 늘어난 몸 길이를 돌려준다.
+    pub fn eat(&mut self, client_id: usize, food_id: u32) -> Option<usize> {
+        let worm = self.worms.get_mut(&client_id).filter(|worm| !worm.is_dead)?;
+        let food = self.foods.get(&food_id)?;
+        if worm.head.distance(food.position) > worm.thickness() * 2.0 + Self::EAT_TOLERANCE {
+            return None;
+        }
+
+        let growth = food.growth;
+        self.food_grid.remove(food_id, food.position);
+        self.foods.remove(&food_id);
+        worm.grow(growth);
+        Some(growth)
+    }
+
+    // 죽은 지렁이를 월드에서 빼고 몸통을 먹이로 바꾼다. 이미 없는 지렁이면 false.
+    // 같은 연결에서 다시 ReqJoin을 보내면 같은 id로 다시 생성된다.
+    pub fn kill(&mut self, client_id: usize) -> bool {
+        let Some(worm) = self.worms.remove(&client_id) else {
+            return false;
+        };
+        // ResDie는 모두에게 가므로 시야에서 따로 빼줄 필요가 없다. 죽은 사람의 시야는 그대로 둔다.
+        self.forget_worm(client_id);
+        // 너무 많은 먹이가 한 번에 생기지 않도록 몸통 점을 몇 칸씩 건너뛴다.
+        for position in worm.points.iter().step_by(Self::BODY_FOOD_STEP) {
+            self.add_food(*position, Food::random_growth());
+        }
+        true
+    }
+
+    fn forget_worm(&mut self, client_id: usize) {
+        for interest in self.interests.values_mut() {
+            interest.worms.remove(&client_id);
+        }
+    }
+
+    // 모든 클라이언트의 시야를 다시 계산해서 새로 보이거나 안 보이게 된 것을 돌려준다.
+    pub fn update_interests(&mut self) -> Vec<ViewChanges> {
+        let worm_grid = self.worm_grid();
+        let spawned_foods = std::mem::take(&mut self.spawned_foods);
+        let client_ids = self.interests.keys().copied().collect::<Vec<_>>();
+        client_ids.into_iter()
+            .filter_map(|client_id| self.update_interest(client_id, &worm_grid, &spawned_foods))
+            .collect()
+    }
+
+    // 막 참가한 클라이언트의 시야. 스냅샷에 담아 보내므로 새로 생긴 먹이도 따로 나누지 않는다.
+    pub fn view(&mut self, client_id: usize) -> Option<ViewChanges> {
+        let worm_grid = self.worm_grid();
+        self.update_interest(client_id, &worm_grid, &HashSet::new())
+    }
+
+    // 몸통 점마다 칸에 넣어두고, 시야와 겹치는 칸에 점이 하나라도 있는 지렁이를 보이는 것으로 본다.
+    fn worm_grid(&self) -> SpatialGrid<usize> {
+        let mut grid = SpatialGrid::new(interest::CELL_SIZE);
+        for worm in self.alive_worms() {
+            for point in worm.points.iter() {
+                grid.insert(worm.client_id, *point);
+            }
+        }
+        grid
+    }
+
+    fn update_interest(&mut self, client_id: usize, worm_grid: &SpatialGrid<usize>, spawned_foods: &HashSet<u32>) -> Option<ViewChanges> {
+        let worm = self.worms.get(&client_id).filter(|worm| !worm.is_dead);
+        let interest = self.interests.get_mut(&client_id)?;
+        if let Some(worm) = worm {
+            interest.area = Some(InterestArea::around(worm));
+        }
+        let area = interest.area?;
+        let enter_area = area.expanded(interest::ENTER_MARGIN);
+        let leave_area = area.expanded(interest::LEAVE_MARGIN);
+
+        let mut changes = ViewChanges { client_id, ..Default::default() };
+
+        let staying_worms = worm_grid.query_rect(leave_area.min(), leave_area.max())
+            .map(|(id, _)| id)
+            .collect::<HashSet<_>>();
+        interest.worms.retain(|id| {
+            if staying_worms.contains(id) {
+                changes.visible_worms.push(*id);
+                true
+            } else {
+                changes.left_worms.push(*id);
+                false
+            }
+        });
+        for (id, _) in worm_grid.query_rect(enter_area.min(), enter_area.max()) {
+            if id != client_id && interest.worms.insert(id) {
+                changes.entered_worms.push(self.worms[&id].to_worm_body());
+            }
+        }
+
+        let foods = &self.foods;
+        interest.foods.retain(|id| match foods.get(id) {
+            Some(food) if leave_area.contains(food.position) => true,
+            Some(_) => {
+                changes.left_foods.push(*id);
+                false
+            },
+            None => {
+                changes.eaten_foods.push(*id);
+                false
+            },
+        });
+        for (id, _) in self.food_grid.query_rect(enter_area.min(), enter_area.max()) {
+            if !interest.foods.insert(id) {
+                continue;
+            }
+            if spawned_foods.contains(&id) {
+                changes.spawned_foods.push(foods[&id].to_food_info());
+            } else {
+                changes.entered_foods.push(foods[&id].to_food_info());
+            }
+        }
+
+        Some(changes)
+    }
+
+    // 모든 지렁이를 한 tick 진행하고, 이번 tick에 죽은 지렁이를 돌려준다.
+    pub fn step(&mut self, dt: f32) -> Vec<Death> {
+        let mut died = Vec::new();
+
+        for worm in self.worms.values_mut() {
+            if worm.is_dead {
+                continue;
+            }
+
+            worm.step(dt);
+
+            for zone in self.damage_zones.iter() {
+                if worm.head.distance(zone.center) <= zone.radius {
+                    worm.take_damage(zone.damage_per_sec * dt);
+                }
+            }
+
+            if worm.is_outside(self.map_radius) {
+                worm.is_dead = true;
+                died.push(Death { client_id: worm.client_id, killer_id: None });
+            }
+        }
+
+        // 모두 움직인 뒤에 충돌을 본다. 머리끼리 부딪히면 둘 다 죽는다.
+        let collisions = self.alive_worms()
+            .filter_map(|worm| {
+                self.alive_worms()
+                    .find(|other| other.client_id != worm.client_id && worm.hits(other))
+                    .map(|other| Death { client_id: worm.client_id, killer_id: Some(other.client_id) })
+            })
+            .collect::<Vec<_>>();
+
+        for death in collisions.iter() {
+            if let Some(worm) = self.worms.get_mut(&death.client_id) {
+                worm.is_dead = true;
+            }
+            if let Some(killer) = death.killer_id.and_then(|killer_id| self.worms.get_mut(&killer_id)) {
+                killer.kills += 1;
+            }
+        }
+        died.extend(collisions);
+
+        died
+    }
+
+    fn overlaps_other(&self, worm: &WormState) -> bool {
+        self.alive_worms().any(|other| other.client_id != worm.client_id && (worm.hits(other) || other.hits(worm)))
+    }
+
+    pub fn alive_worms(&self) -> impl Iterator<Item = &WormState> {
+        self.worms.values().filter(|worm| !worm.is_dead)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eat_validation() {
+        let mut world = World::new(&WorldSettings::default());
+        assert_eq!(world.foods.len(), World::FOOD_COUNT);
+
+        let head = world.spawn(1).head;
+        let near = world.add_food(head + Vec2::new(30.0, 0.0), 2);
+        let far = world.add_food(head + Vec2::new(1000.0, 0.0), 2);
+
+        assert_eq!(world.eat(1, far), None);
+        assert_eq!(world.eat(2, near), None);
+        assert_eq!(world.eat(1, near), Some(2));
+        // 이미 먹힌 먹이
+        assert_eq!(world.eat(1, near), None);
+        assert_eq!(world.worm(1).unwrap().max_points, WormState::INITIAL_MAX_POINTS + 2);
+    }
+
+    #[test]
+    fn test_respawn_only_dead_worm() {
+        let mut world = World::new(&WorldSettings::default());
+        assert!(world.respawn(1).is_some());
+        let head = world.worm(1).unwrap().head;
+
+        // 살아있는 동안에는 다시 참가해도 그 자리에 있다
+        assert!(world.respawn(1).is_none());
+        assert_eq!(world.worm(1).unwrap().head, head);
+
+        world.worm_mut(1).unwrap().is_dead = true;
+        assert!(world.respawn(1).is_some());
+        assert!(!world.worm(1).unwrap().is_dead);
+    }
+
+    #[test]
+    fn test_spawn_avoids_other_bodies() {
+        let mut world = World::new(&WorldSettings { map_radius: 500.0, ..WorldSettings::default() });
+
+        // 맵 가운데를 빽빽하게 덮는 지렁이. 한 번만 뽑으면 거의 항상 어딘가에서 겹친다
+        world.spawn(1);
+        let blocker = world.worm_mut(1).unwrap();
+        blocker.points = (-10..=10)
+            .flat_map(|x| (-10..=10).map(move |y| Vec2::new(x as f32, y as f32) * WormState::SAMPLE_DISTANCE))
+            .collect();
+        blocker.head = Vec2::ZERO;
+
+        for _ in 0..50 {
+            world.spawn(2);
+            let worm = world.worm(2).unwrap();
+            assert!(!worm.hits(world.worm(1).unwrap()));
+            assert!(!world.worm(1).unwrap().hits(worm));
+        }
+    }
+
+    #[test]
+    fn test_interest_enter_and_leave() {
+        let mut world = World::new(&WorldSettings::default());
+        let head = world.spawn(1).head;
+        world.worm_mut(1).unwrap().client_reported = true;
+        world.view(1);
+
+        let food = world.add_food(head + Vec2::new(30.0, 0.0), 1);
+        world.spawn(2);
+        let other = world.worm_mut(2).unwrap();
+        other.client_reported = true;
+        other.points = [head + Vec2::new(100.0, 100.0)].into();
+
+        let changes_of = |world: &mut World| world.update_interests().into_iter().find(|changes| changes.client_id == 1).unwrap();
+
+        let changes = changes_of(&mut world);
+        // 시야 안에 새로 생긴 먹이는 들어온 먹이와 따로 나온다
+        assert!(changes.spawned_foods.iter().any(|info| info.id == food));
+        assert!(changes.entered_foods.iter().all(|info| info.id != food));
+        assert_eq!(changes.entered_worms.iter().map(|body| body.client_id()).collect::<Vec<_>>(), vec![2]);
+
+        // 먹힌 먹이와 멀리 간 지렁이
+        world.eat(1, food);
+        world.worm_mut(2).unwrap().points = [head + Vec2::new(4000.0, 0.0)].into();
+        let changes = changes_of(&mut world);
+        assert_eq!(changes.eaten_foods, vec![food]);
+        assert_eq!(changes.left_worms, vec![2]);
+        assert!(changes.visible_worms.is_empty());
+    }
+
+    #[test]
+    fn test_head_hits_other_body() {
+        let mut world = World::new(&WorldSettings::default());
+        let body = world.spawn(1).points.clone();
+        world.spawn(2);
+
+        // 둘 다 움직이지 않게 하고, 2번 머리를 1번 몸통 한가운데에 둔다.
+        let middle = body[body.len() / 2];
+        world.worm_mut(1).unwrap().client_reported = true;
+        let victim = world.worm_mut(2).unwrap();
+        victim.client_reported = true;
+        victim.head = middle;
+        victim.points = [middle].into();
+
+        assert_eq!(world.step(0.1), vec![Death { client_id: 2, killer_id: Some(1) }]);
+        assert_eq!(world.worm(1).unwrap().kills, 1);
+        assert!(world.worm(2).unwrap().is_dead);
+    }
+
+    #[test]
+    fn test_kill_turns_body_into_food() {
+        let mut world = World::new(&WorldSettings::default());
+        let points = world.spawn(1).points.clone();
+
+        assert!(world.kill(1));
+        assert!(world.worm(1).is_none());
+        assert!(!world.kill(1));
+
+        let body_foods = world.foods.values().filter(|food| points.contains(&food.position)).count();
+        assert_eq!(body_foods, points.len().div_ceil(World::BODY_FOOD_STEP));
+    }
+}
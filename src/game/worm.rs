@@ -0,0 +1,281 @@
+use crate::network::message::worm_body::WormBody;
+use glam::Vec2;
+use rand::Rng;
+use std::collections::VecDeque;
+use std::time::Instant;
+
+// 서버가 들고 있는 지렁이 한 마리의 상태.
+// 클라이언트의 `Worm` 리소스와 같은 규칙으로 움직이지만, 방향/부스트는 클라이언트 입력으로만 바뀐다.
+#[derive(Debug, Clone)]
+pub struct WormState {
+    pub client_id: usize,
+    pub color: (f32, f32, f32, f32),
+
+    pub head: Vec2,
+    pub dir: Vec2,              // 항상 정규화된 방향
+    pub target_dir: Vec2,       // 클라이언트가 보낸 목표 방향
+
+    pub last_input_seq: u32,    // 마지막으로 반영한 클라이언트 입력 번호
+    pub boost_input: bool,      // 클라이언트가 부스트 키를 누르고 있는지
+    pub is_boosting: bool,      // 실제로 부스트 중인지
+    pub boost_remaining: f32,   // 남은 부스트 시간
+    pub boost_available: bool,  // 완전 충전 전까지 재사용 금지
+
+    pub points: VecDeque<Vec2>, // 머리 위치 히스토리 = 몸통
+    pub max_points: usize,      // 몸 길이 (샘플 수)
+    pub damage_accumulator: f32,
+
+    pub is_dead: bool,
+    pub kills: u32,             // 이 지렁이 몸통에 부딪혀 죽은 지렁이 수
+    // ReqMove로 몸통을 직접 보고하는 클라이언트의 지렁이는 서버가 움직이지 않는다.
+    pub client_reported: bool,
+    pub reported_at: Instant,   // 마지막으로 받아준 몸통 보고 (없으면 생성 시각)
+}
+
+impl WormState {
+    pub const BASE_SPEED: f32 = 220.0;
+    pub const BOOST_SPEED: f32 = 350.0;
+    pub const BOOST_MAX: f32 = 3.0;
+    pub const BOOST_RECHARGE: f32 = 0.4;
+    pub const TURN_SPEED: f32 = 3.0;
+    pub const SAMPLE_DISTANCE: f32 = 6.0;
+    pub const GROWTH_PER_DOT: usize = 1;
+    pub const MIN_POINTS: usize = 16;
+    pub const INITIAL_MAX_POINTS: usize = Self::MIN_POINTS;
+
+    // 맵 안쪽 임의의 위치에 임의의 방향과 색으로 생성
+    pub fn spawn(client_id: usize, map_radius: f32) -> Self {
+        let mut rng = rand::rng();
+
+        let angle = rng.random_range(0.0..std::f32::consts::TAU);
+        let dir = Vec2::from_angle(angle);
+
+        let spawn_radius = map_radius * 0.8;
+        let body_length = Self::SAMPLE_DISTANCE * Self::INITIAL_MAX_POINTS as f32;
+        let safe_radius = spawn_radius - body_length;
+        let r = rng.random_range(0.0..1.0f32).sqrt() * safe_radius.max(0.0);
+        let pos_angle = rng.random_range(0.0..std::f32::consts::TAU);
+        let head = Vec2::new(r * pos_angle.cos(), r * pos_angle.sin());
+
+        let mut points = VecDeque::new();
+        for i in (0..Self::INITIAL_MAX_POINTS).rev() {
+            points.push_back(head + dir * (-Self::SAMPLE_DISTANCE * i as f32));
+        }
+
+        let color = (
+            rng.random_range(0.3..1.0),
+            rng.random_range(0.3..1.0),
+            rng.random_range(0.3..1.0),
+            1.0,
+        );
+
+        Self {
+            client_id,
+            color,
+            head,
+            dir,
+            target_dir: dir,
+            last_input_seq: 0,
+            boost_input: false,
+            is_boosting: false,
+            boost_remaining: Self::BOOST_MAX,
+            boost_available: true,
+            points,
+            max_points: Self::INITIAL_MAX_POINTS,
+            damage_accumulator: 0.0,
+            is_dead: false,
+            kills: 0,
+            client_reported: false,
+            reported_at: Instant::now(),
+        }
+    }
+
+    pub fn apply_input(&mut self, seq: u32, target_dir: Vec2, boost: bool) {
+        self.last_input_seq = seq;
+        // 길이가 0이거나 NaN인 방향은 무시하고 기존 목표를 유지
+        if let Some(target_dir) = target_dir.try_normalize() {
+            self.target_dir = target_dir;
+        }
+        self.boost_input = boost;
+    }
+
+    // 한 tick 만큼 회전, 부스트 게이지, 이동을 처리
+    pub fn step(&mut self, dt: f32) {
+        if self.is_dead || self.client_reported {
+            return;
+        }
+
+        // dir이 target_dir을 부드럽게 따라감 (slerp)
+        let t = (Self::TURN_SPEED * dt).clamp(0.0, 1.0);
+        let angle = self.dir.angle_to(self.target_dir);
+        self.dir = Vec2::from_angle(angle * t).rotate(self.dir).normalize_or(self.dir);
+
+        self.is_boosting = self.boost_input && self.boost_available && self.boost_remaining > 0.0;
+        if self.is_boosting {
+            self.drain_boost(dt);
+        } else {
+            self.recharge_boost(dt);
+        }
+
+        let speed = if self.is_boosting { Self::BOOST_SPEED } else { Self::BASE_SPEED };
+        let new_head = self.head + self.dir * speed * dt;
+
+        let push = match self.points.back() {
+            Some(last) => new_head.distance(*last) >= Self::SAMPLE_DISTANCE,
+            None => true,
+        };
+
+        self.head = new_head;
+
+        if push {
+            self.points.push_back(new_head);
+            while self.points.len() > self.max_points {
+                self.points.pop_front();
+            }
+        }
+    }
+
+    // 몸통을 직접 보고하는 지렁이는 step을 거치지 않으므로, 보고 사이에 움직인 거리로 게이지를 계산한다.
+    // 기본 속도보다 더 간 만큼을 부스트한 시간으로 보고, 나머지 시간은 충전한다.
+    pub fn spend_reported_boost(&mut self, distance: f32, dt: f32) {
+        let boost_secs = if self.boost_available {
+            ((distance - Self::BASE_SPEED * dt) / (Self::BOOST_SPEED - Self::BASE_SPEED)).clamp(0.0, dt)
+        } else {
+            0.0
+        };
+        self.is_boosting = boost_secs > 0.0;
+        if self.is_boosting {
+            self.drain_boost(boost_secs);
+        }
+        self.recharge_boost(dt - boost_secs);
+    }
+
+    fn drain_boost(&mut self, dt: f32) {
+        self.boost_remaining = (self.boost_remaining - dt).max(0.0);
+        if self.boost_remaining <= 0.0 {
+            self.boost_available = false;
+        }
+    }
+
+    fn recharge_boost(&mut self, dt: f32) {
+        self.boost_remaining = (self.boost_remaining + Self::BOOST_RECHARGE * dt).min(Self::BOOST_MAX);
+        if self.boost_remaining >= Self::BOOST_MAX {
+            self.boost_available = true;
+        }
+    }
+
+    pub fn grow(&mut self, points: usize) {
+        self.max_points += points * Self::GROWTH_PER_DOT;
+    }
+
+    // 누적 데미지 1당 몸 길이 1 감소. 최소 길이보다 길면 true
+    pub fn take_damage(&mut self, amount: f32) -> bool {
+        self.damage_accumulator += amount;
+
+        while self.damage_accumulator >= 1.0 {
+            self.damage_accumulator -= 1.0;
+
+            if self.max_points > Self::MIN_POINTS {
+                self.max_points -= 1;
+
+                if self.points.len() > self.max_points {
+                    self.points.pop_front();
+                }
+            }
+        }
+
+        self.max_points > Self::MIN_POINTS
+    }
+
+    // 클라이언트가 그리는 몸 두께와 같은 값
+    pub fn thickness(&self) -> f32 {
+        (16.0 + self.points.len() as f32 * 0.24).clamp(16.0, 72.0)
+    }
+
+    // 내 머리가 다른 지렁이 몸통(머리 포함)에 닿았는지. 양쪽 두께의 절반을 더한 거리 안이면 닿은 것으로 본다.
+    pub fn hits(&self, other: &WormState) -> bool {
+        let reach = (self.thickness() + other.thickness()) * 0.5;
+        other.points.iter().any(|point| point.distance(self.head) <= reach)
+    }
+
+    pub fn is_outside(&self, map_radius: f32) -> bool {
+        self.head.length() > map_radius
+    }
+
+    pub fn to_worm_body(&self) -> WormBody {
+        WormBody::from_parts(self.client_id, self.color, self.points.iter().map(|p| (p.x, p.y)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WormState;
+    use glam::Vec2;
+
+    #[test]
+    fn test_step_moves_at_base_speed() {
+        let mut worm = WormState::spawn(1, 2500.0);
+        let head = worm.head;
+
+        worm.step(0.1);
+
+        assert!((worm.head.distance(head) - WormState::BASE_SPEED * 0.1).abs() < 1e-3);
+        assert!(!worm.is_boosting);
+    }
+
+    #[test]
+    fn test_boost_runs_out_and_recharges() {
+        let mut worm = WormState::spawn(1, 2500.0);
+        worm.apply_input(1, worm.dir, true);
+
+        for _ in 0..40 {
+            worm.step(0.1);
+        }
+        assert!(!worm.boost_available);
+        assert!(!worm.is_boosting);
+
+        worm.apply_input(2, worm.dir, false);
+        for _ in 0..((WormState::BOOST_MAX / WormState::BOOST_RECHARGE / 0.1) as usize + 1) {
+            worm.step(0.1);
+        }
+        assert!(worm.boost_available);
+    }
+
+    #[test]
+    fn test_reported_boost_spends_gauge() {
+        let mut worm = WormState::spawn(1, 2500.0);
+
+        for _ in 0..40 {
+            worm.spend_reported_boost(WormState::BOOST_SPEED * 0.1, 0.1);
+        }
+        assert!(!worm.boost_available);
+
+        // 기본 속도로만 움직이면 충전된다
+        for _ in 0..((WormState::BOOST_MAX / WormState::BOOST_RECHARGE / 0.1) as usize + 1) {
+            worm.spend_reported_boost(WormState::BASE_SPEED * 0.1, 0.1);
+        }
+        assert!(worm.boost_available);
+    }
+
+    #[test]
+    fn test_turns_towards_target_dir() {
+        let mut worm = WormState::spawn(1, 2500.0);
+        let target = Vec2::new(-worm.dir.y, worm.dir.x);
+        worm.apply_input(1, target, false);
+
+        for _ in 0..60 {
+            worm.step(1.0 / 30.0);
+        }
+        assert!(worm.dir.dot(target) > 0.99);
+    }
+
+    #[test]
+    fn test_damage_never_shrinks_below_min_points() {
+        let mut worm = WormState::spawn(1, 2500.0);
+        worm.grow(3);
+
+        assert!(worm.take_damage(2.5));
+        assert!(!worm.take_damage(10.0));
+        assert_eq!(worm.max_points, WormState::MIN_POINTS);
+    }
+}
@@ -0,0 +1,161 @@
+use bug::network::message::message_from_server::MessageFromServer;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+// 클라이언트 한 명당 쌓아둘 수 있는 미전송 메세지 수.
+// 이보다 많이 밀리면 느린 클라이언트로 보고 메세지를 버린다.
+const OUTBOUND_QUEUE_SIZE: usize = 256;
+
+// 같은 응답을 여러 클라이언트에게 보낼 때 메세지를 복사하지 않도록 Arc로 공유.
+// 바이트로 바꾸는 건 각 연결 태스크의 코덱이 한다.
+pub type Outbound = Arc<MessageFromServer>;
+
+// 응답을 누구에게 보낼지
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recipient {
+    Sender,     // 요청을 보낸 클라이언트에게만
+    All,        // 같은 방에 있는 모든 클라이언트에게
+}
+
+// 접속한 클라이언트마다 채널 송신자를 하나씩 들고 있는 허브.
+// 각 연결 태스크는 register()로 받은 리시버를 소켓 수신과 함께 select! 하면서,
+// 다른 연결 태스크가 허브를 통해 보낸 메세지를 자신의 소켓으로 써준다.
+#[derive(Clone, Default)]
+pub struct Hub {
+    clients: Arc<Mutex<Clients>>,
+}
+
+#[derive(Default)]
+struct Clients {
+    handles: HashMap<SocketAddr, ClientHandle>,
+    // tick마다 id로 보내는 메세지가 많으므로 연결을 훑지 않고 찾도록 id로도 색인해둔다
+    by_client_id: HashMap<usize, SocketAddr>,
+}
+
+struct ClientHandle {
+    tx: mpsc::Sender<Outbound>,
+    client_id: Option<usize>,   // 게임에 참가한 뒤에만 있음
+    room: String,               // 지금 들어가 있는 방 이름
+}
+
+impl Hub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&self, client_access_info: SocketAddr, room: &str) -> mpsc::Receiver<Outbound> {
+        let (tx, rx) = mpsc::channel(OUTBOUND_QUEUE_SIZE);
+        let mut clients = self.clients.lock().unwrap();
+        let old = clients.handles.insert(client_access_info, ClientHandle { tx, client_id: None, room: room.to_string() });
+        if let Some(client_id) = old.and_then(|handle| handle.client_id) {
+            clients.unbind(client_id, &client_access_info);
+        }
+        rx
+    }
+
+    // 연결에 게임 속 client id를 연결해두면, tick 루프처럼 연결을 모르는 곳에서도 id로 보낼 수 있다.
+    pub fn bind_client_id(&self, client_access_info: &SocketAddr, client_id: Option<usize>) {
+        let mut clients = self.clients.lock().unwrap();
+        let Some(handle) = clients.handles.get_mut(client_access_info) else {
+            return;
+        };
+        let old = std::mem::replace(&mut handle.client_id, client_id);
+        if let Some(old) = old {
+            clients.unbind(old, client_access_info);
+        }
+        if let Some(client_id) = client_id {
+            clients.by_client_id.insert(client_id, *client_access_info);
+        }
+    }
+
+    // 방을 옮기면 그 다음부터는 새 방의 브로드캐스트만 받는다.
+    pub fn bind_room(&self, client_access_info: &SocketAddr, room: &str) {
+        if let Some(handle) = self.clients.lock().unwrap().handles.get_mut(client_access_info) {
+            handle.room = room.to_string();
+        }
+    }
+
+    pub fn unregister(&self, client_access_info: &SocketAddr) {
+        let mut clients = self.clients.lock().unwrap();
+        if let Some(client_id) = clients.handles.remove(client_access_info).and_then(|handle| handle.client_id) {
+            clients.unbind(client_id, client_access_info);
+        }
+    }
+
+    pub fn dispatch(&self, sender: &SocketAddr, recipient: Recipient, msg: MessageFromServer) {
+        match recipient {
+            Recipient::Sender => self.send_to(sender, msg),
+            Recipient::All => {
+                let room = self.clients.lock().unwrap().handles.get(sender).map(|handle| handle.room.clone());
+                if let Some(room) = room {
+                    self.broadcast_room(&room, msg);
+                }
+            },
+        }
+    }
+
+    pub fn send_to(&self, client_access_info: &SocketAddr, msg: MessageFromServer) {
+        let clients = self.clients.lock().unwrap();
+        if let Some(handle) = clients.handles.get(client_access_info) {
+            Self::push(client_access_info, &handle.tx, Arc::new(msg));
+        }
+    }
+
+    pub fn send_to_client_id(&self, client_id: usize, msg: MessageFromServer) {
+        let clients = self.clients.lock().unwrap();
+        if let Some((client_access_info, handle)) = clients.find(client_id) {
+            Self::push(client_access_info, &handle.tx, Arc::new(msg));
+        }
+    }
+
+    // 게임 속 client id가 client_ids에 있는 연결에게만 같은 메세지를 보낸다
+    pub fn multicast(&self, client_ids: &HashSet<usize>, msg: MessageFromServer) {
+        let msg = Arc::new(msg);
+        let clients = self.clients.lock().unwrap();
+        for (client_access_info, handle) in client_ids.iter().filter_map(|&client_id| clients.find(client_id)) {
+            Self::push(client_access_info, &handle.tx, msg.clone());
+        }
+    }
+
+    pub fn broadcast_room(&self, room: &str, msg: MessageFromServer) {
+        let msg = Arc::new(msg);
+        let clients = self.clients.lock().unwrap();
+        for (client_access_info, handle) in clients.handles.iter().filter(|(_, handle)| handle.room == room) {
+            Self::push(client_access_info, &handle.tx, msg.clone());
+        }
+    }
+
+    // 방과 상관없이 접속한 모든 클라이언트에게. 서버 종료처럼 모두가 알아야 하는 경우에만 쓴다.
+    pub fn broadcast(&self, msg: MessageFromServer) {
+        let msg = Arc::new(msg);
+        let clients = self.clients.lock().unwrap();
+        for (client_access_info, handle) in clients.handles.iter() {
+            Self::push(client_access_info, &handle.tx, msg.clone());
+        }
+    }
+
+    fn push(client_access_info: &SocketAddr, tx: &mpsc::Sender<Outbound>, msg: Outbound) {
+        // 락을 잡은 채로 기다리지 않도록 try_send 사용.
+        // 채널이 닫힌 경우는 연결 태스크가 종료 중인 것이므로 무시한다.
+        if let Err(mpsc::error::TrySendError::Full(_)) = tx.try_send(msg) {
+            warn!("[{}] outbound queue is full. dropping message.", client_access_info);
+        }
+    }
+}
+
+impl Clients {
+    fn find(&self, client_id: usize) -> Option<(&SocketAddr, &ClientHandle)> {
+        let client_access_info = self.by_client_id.get(&client_id)?;
+        self.handles.get_key_value(client_access_info)
+    }
+
+    // 같은 id를 이어받은 다른 연결이 있으면 그쪽 색인은 남긴다
+    fn unbind(&mut self, client_id: usize, client_access_info: &SocketAddr) {
+        if self.by_client_id.get(&client_id) == Some(client_access_info) {
+            self.by_client_id.remove(&client_id);
+        }
+    }
+}
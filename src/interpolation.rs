@@ -0,0 +1,164 @@
+use crate::RemoteWorms;
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+// 다른 지렁이들은 네트워크 주기로만 위치가 오므로, 받은 몸통들을 시간과 함께 쌓아두고
+// 조금 늦은 시점(delay)을 기준으로 두 스냅샷 사이를 보간해서 그린다.
+pub struct InterpolationPlugin;
+
+impl Plugin for InterpolationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InterpolationSettings>()
+            .add_systems(Update, interpolate_remote_worms.before(crate::redraw_remote_worms));
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct InterpolationSettings {
+    pub delay: f64,             // 현재 시각보다 얼마나 늦게 그릴지 (초). 패킷 간격의 2~3배 정도가 적당하다.
+    pub max_extrapolation: f64, // 패킷이 늦을 때 마지막 움직임을 이어서 예측해줄 최대 시간 (초)
+}
+
+impl Default for InterpolationSettings {
+    fn default() -> Self {
+        Self {
+            delay: 0.1,
+            max_extrapolation: 0.25,
+        }
+    }
+}
+
+struct Snapshot {
+    time: f64,
+    points: Vec<Vec2>,
+}
+
+// 지렁이 한 마리의 수신 시각별 몸통 스냅샷
+#[derive(Default)]
+pub struct SnapshotBuffer {
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl SnapshotBuffer {
+    const CAPACITY: usize = 32;
+
+    pub fn push(&mut self, time: f64, points: Vec<Vec2>) {
+        // 같은 프레임에 여러 개가 오거나 순서가 뒤집힌 경우 최신 것으로 덮어쓴다.
+        if let Some(last) = self.snapshots.back_mut()
+            && time <= last.time
+        {
+            last.points = points;
+            return;
+        }
+
+        self.snapshots.push_back(Snapshot { time, points });
+        while self.snapshots.len() > Self::CAPACITY {
+            self.snapshots.pop_front();
+        }
+    }
+
+    // render_time 시점의 몸통. 두 스냅샷 사이면 보간하고, 마지막 스냅샷 이후면 max_extrapolation 까지만 외삽한다.
+    pub fn sample(&self, render_time: f64, max_extrapolation: f64) -> Option<Vec<Vec2>> {
+        let first = self.snapshots.front()?;
+        let last = self.snapshots.back()?;
+
+        if render_time <= first.time {
+            return Some(first.points.clone());
+        }
+
+        if render_time >= last.time {
+            let Some(prev) = self.snapshots.iter().rev().nth(1) else {
+                return Some(last.points.clone());
+            };
+            let extra = (render_time - last.time).min(max_extrapolation);
+            let t = 1.0 + extra / (last.time - prev.time);
+            return Some(lerp_bodies(&prev.points, &last.points, t as f32));
+        }
+
+        // render_time을 사이에 둔 두 스냅샷을 찾아 보간
+        let (from, to) = self.snapshots.iter()
+            .zip(self.snapshots.iter().skip(1))
+            .find(|(_, to)| render_time < to.time)?;
+        let t = (render_time - from.time) / (to.time - from.time);
+        Some(lerp_bodies(&from.points, &to.points, t as f32))
+    }
+
+    // 보간에 더 이상 쓰이지 않는 오래된 스냅샷 정리 (render_time 이전 것은 하나만 남긴다)
+    pub fn prune(&mut self, render_time: f64) {
+        while self.snapshots.len() > 2 && self.snapshots[1].time <= render_time {
+            self.snapshots.pop_front();
+        }
+    }
+}
+
+// 몸통 점들은 꼬리 -> 머리 순서이고 머리 쪽에 새 점이 붙으므로, 머리부터 같은 순번끼리 보간한다.
+// 길이는 새 스냅샷(to)을 따른다.
+fn lerp_bodies(from: &[Vec2], to: &[Vec2], t: f32) -> Vec<Vec2> {
+    let mut points = Vec::with_capacity(to.len());
+    for (k, p) in to.iter().enumerate() {
+        let from_index = (from.len() + k).checked_sub(to.len());
+        match from_index.and_then(|i| from.get(i)) {
+            Some(q) => points.push(q.lerp(*p, t)),
+            None => points.push(*p),
+        }
+    }
+    points
+}
+
+fn interpolate_remote_worms(time: Res<Time>, settings: Res<InterpolationSettings>, mut remote: ResMut<RemoteWorms>) {
+    if remote.worms.is_empty() {
+        return;
+    }
+
+    let render_time = time.elapsed_secs_f64() - settings.delay;
+    for worm in remote.worms.iter_mut() {
+        if let Some(points) = worm.snapshots.sample(render_time, settings.max_extrapolation) {
+            worm.points = points;
+        }
+        worm.snapshots.prune(render_time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SnapshotBuffer;
+    use bevy::prelude::Vec2;
+
+    fn body(head_x: f32) -> Vec<Vec2> {
+        (0..4).map(|i| Vec2::new(head_x - (3 - i) as f32 * 6.0, 0.0)).collect()
+    }
+
+    #[test]
+    fn test_interpolates_between_snapshots() {
+        let mut buffer = SnapshotBuffer::default();
+        buffer.push(1.0, body(0.0));
+        buffer.push(1.1, body(10.0));
+
+        let points = buffer.sample(1.05, 0.25).unwrap();
+        assert_eq!(points.len(), 4);
+        assert!((points[3].x - 5.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_extrapolation_is_bounded() {
+        let mut buffer = SnapshotBuffer::default();
+        buffer.push(1.0, body(0.0));
+        buffer.push(1.1, body(10.0));
+
+        // 0.1초에 10만큼 움직였으므로, 최대 0.2초까지만 이어서 20을 더 간다.
+        let points = buffer.sample(5.0, 0.2).unwrap();
+        assert!((points[3].x - 30.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_prune_keeps_snapshot_before_render_time() {
+        let mut buffer = SnapshotBuffer::default();
+        for i in 0..5 {
+            buffer.push(i as f64, body(i as f32));
+        }
+
+        buffer.prune(3.5);
+        let points = buffer.sample(3.5, 0.0).unwrap();
+        assert!((points[3].x - 3.5).abs() < 1e-4);
+    }
+}
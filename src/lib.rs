@@ -0,0 +1,5 @@
+// 클라이언트와 서버가 함께 쓰는 프로토콜 코드와 게임 규칙.
+// Bevy 타입(Vec2, Srgba)과의 변환은 `bevy` feature를 켰을 때만 포함되므로,
+// 서버는 `--no-default-features`로 빌드하면 Bevy 없이 빌드된다.
+pub mod network;
+pub mod game;
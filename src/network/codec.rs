@@ -0,0 +1,412 @@
+use crate::network::error::{CodecError, NetworkError, ProtocolError};
+use crate::network::message::message_from_client::MessageFromClient;
+use crate::network::message::message_from_server::MessageFromServer;
+use crate::network::protocol::{Capabilities, WireFormat};
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+// 프레임 형식
+//  길이               |   유형  |   메세지
+// 2 bytes 또는 varint | 1 byte | N bytes ..
+// 길이 필드에는 유형 + 메세지의 바이트 수가 들어있다.
+const U16_LENGTH_FIELD_SIZE: usize = 2;
+// u32를 LEB128로 쓰면 최대 5바이트
+const MAX_VARINT_LENGTH_FIELD_SIZE: usize = 5;
+
+// 긴 지렁이 몸통도 한 프레임에 들어가도록 1 MiB. u16 길이 필드에서는 u16 범위가 한계다.
+pub const DEFAULT_MAX_FRAME_LENGTH: usize = 1024 * 1024;
+
+// 길이 필드 형식. 처음에는 모두 u16으로 시작하고, 인사에서 VARINT_FRAMES를 협상하면 그 뒤부터 varint로 바꾼다.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FrameLength {
+    #[default]
+    U16,        // 2 bytes, big endian
+    Varint,     // LEB128, 1 ~ 5 bytes (u32 범위)
+}
+
+impl FrameLength {
+    pub fn negotiated(capabilities: Capabilities) -> Self {
+        if capabilities.contains(Capabilities::VARINT_FRAMES) {
+            FrameLength::Varint
+        } else {
+            FrameLength::U16
+        }
+    }
+
+    // 이 형식의 길이 필드에 담을 수 있는 가장 긴 프레임
+    fn limit(self) -> usize {
+        match self {
+            FrameLength::U16 => u16::MAX as usize,
+            FrameLength::Varint => u32::MAX as usize,
+        }
+    }
+}
+
+// 서버 쪽 코덱. 클라이언트 메세지를 읽고 서버 메세지를 쓴다.
+// `Framed<TcpStream, ServerCodec>`으로 감싸면 메세지 단위 Stream / Sink로 쓸 수 있다.
+#[derive(Debug, Clone)]
+pub struct ServerCodec {
+    max_frame_length: usize,
+    frame_length: FrameLength,
+    format: WireFormat,
+}
+
+// 클라이언트 쪽 코덱. 서버 메세지를 읽고 클라이언트 메세지를 쓴다.
+#[derive(Debug, Clone)]
+pub struct ClientCodec {
+    max_frame_length: usize,
+    frame_length: FrameLength,
+    format: WireFormat,
+}
+
+impl ServerCodec {
+    pub fn new() -> Self {
+        Self::with_max_frame_length(DEFAULT_MAX_FRAME_LENGTH)
+    }
+
+    pub fn with_max_frame_length(max_frame_length: usize) -> Self {
+        Self { max_frame_length, frame_length: FrameLength::U16, format: WireFormat::FULL }
+    }
+
+    // 인사에서 협상한 기능에 맞춰 이후 메세지의 몸통 형식을 바꾼다.
+    pub fn set_format(&mut self, format: WireFormat) {
+        self.format = format;
+    }
+
+    // 인사에서 협상한 기능에 맞춰 이후 프레임의 길이 필드 형식을 바꾼다.
+    pub fn set_frame_length(&mut self, frame_length: FrameLength) {
+        self.frame_length = frame_length;
+    }
+}
+
+impl Default for ServerCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClientCodec {
+    pub fn new() -> Self {
+        Self::with_max_frame_length(DEFAULT_MAX_FRAME_LENGTH)
+    }
+
+    pub fn with_max_frame_length(max_frame_length: usize) -> Self {
+        Self { max_frame_length, frame_length: FrameLength::U16, format: WireFormat::FULL }
+    }
+
+    // 인사에서 협상한 기능에 맞춰 이후 메세지의 몸통 형식을 바꾼다.
+    pub fn set_format(&mut self, format: WireFormat) {
+        self.format = format;
+    }
+
+    // 인사에서 협상한 기능에 맞춰 이후 프레임의 길이 필드 형식을 바꾼다.
+    pub fn set_frame_length(&mut self, frame_length: FrameLength) {
+        self.frame_length = frame_length;
+    }
+}
+
+impl Default for ClientCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for ServerCodec {
+    type Item = MessageFromClient;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match decode_frame(src, self.frame_length, self.max_frame_length)? {
+            Some(frame) => Ok(Some(MessageFromClient::new_with(&frame, &self.format)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Encoder<&MessageFromServer> for ServerCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: &MessageFromServer, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        encode_frame(&item.make_message_bytes_with(&self.format), self.frame_length, self.max_frame_length, dst)
+    }
+}
+
+impl Encoder<MessageFromServer> for ServerCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: MessageFromServer, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode(&item, dst)
+    }
+}
+
+impl Decoder for ClientCodec {
+    type Item = MessageFromServer;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match decode_frame(src, self.frame_length, self.max_frame_length)? {
+            Some(frame) => Ok(Some(MessageFromServer::new_with(&frame, &self.format)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Encoder<&MessageFromClient> for ClientCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: &MessageFromClient, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        encode_frame(&item.make_message_bytes_with(&self.format), self.frame_length, self.max_frame_length, dst)
+    }
+}
+
+impl Encoder<MessageFromClient> for ClientCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: MessageFromClient, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode(&item, dst)
+    }
+}
+
+// 버퍼 앞의 길이 필드를 읽어서 (길이, 길이 필드 크기)를 돌려준다. 아직 덜 들어왔으면 None.
+fn decode_length(src: &[u8], frame_length: FrameLength) -> Result<Option<(usize, usize)>, CodecError> {
+    match frame_length {
+        FrameLength::U16 => {
+            let Some(field) = src.first_chunk::<U16_LENGTH_FIELD_SIZE>() else {
+                return Ok(None);
+            };
+            Ok(Some((u16::from_be_bytes(*field) as usize, U16_LENGTH_FIELD_SIZE)))
+        },
+        FrameLength::Varint => {
+            // 하위 7비트씩 낮은 자리부터, 최상위 비트가 1이면 다음 바이트가 이어진다.
+            let mut length = 0u64;
+            for (i, byte) in src.iter().take(MAX_VARINT_LENGTH_FIELD_SIZE).enumerate() {
+                length |= ((byte & 0x7f) as u64) << (7 * i);
+                if byte & 0x80 == 0 {
+                    let length = u32::try_from(length).map_err(|_| CodecError::InvalidFrameLength)?;
+                    return Ok(Some((length as usize, i + 1)));
+                }
+            }
+            // 5바이트를 넘게 이어지는 길이 필드
+            if src.len() >= MAX_VARINT_LENGTH_FIELD_SIZE {
+                return Err(CodecError::InvalidFrameLength);
+            }
+            Ok(None)
+        },
+    }
+}
+
+// 버퍼 앞에 프레임 하나가 다 들어와 있으면 길이 필드를 떼어낸 유형 + 메세지 부분을 잘라서 돌려준다.
+// 아직 덜 들어왔으면 버퍼는 건드리지 않고 None. 나머지 바이트는 다음 프레임이므로 그대로 남겨둔다.
+fn decode_frame(src: &mut BytesMut, frame_length: FrameLength, max_frame_length: usize) -> Result<Option<BytesMut>, CodecError> {
+    let Some((length, field_size)) = decode_length(src, frame_length)? else {
+        return Ok(None);
+    };
+
+    // 다 받기 전에 거른다. 안 그러면 거대한 길이 필드 하나로 버퍼를 키울 수 있다.
+    if length > max_frame_length {
+        return Err(CodecError::FrameTooLarge { length, max_length: max_frame_length });
+    }
+    // 유형 필드조차 없는 프레임
+    if length == 0 {
+        return Err(ProtocolError::from(NetworkError::TooShortMsg).into());
+    }
+
+    let total_length = field_size + length;
+    if src.len() < total_length {
+        // 남은 만큼 미리 잡아두면 다음 read에서 재할당이 줄어든다.
+        src.reserve(total_length - src.len());
+        return Ok(None);
+    }
+
+    src.advance(field_size);
+    Ok(Some(src.split_to(length)))
+}
+
+// 유형 + 메세지 앞에 길이 필드를 붙여서 쓴다.
+// 길이 필드에 담을 수 없거나 최대 길이를 넘으면 잘라서 보내지 않고 에러를 돌려준다.
+fn encode_frame(message: &[u8], frame_length: FrameLength, max_frame_length: usize, dst: &mut BytesMut) -> Result<(), CodecError> {
+    let length = message.len();
+    let max_length = max_frame_length.min(frame_length.limit());
+    if length > max_length {
+        return Err(CodecError::FrameTooLarge { length, max_length });
+    }
+
+    match frame_length {
+        FrameLength::U16 => {
+            dst.reserve(U16_LENGTH_FIELD_SIZE + length);
+            dst.put_u16(length as u16);
+        },
+        FrameLength::Varint => {
+            dst.reserve(MAX_VARINT_LENGTH_FIELD_SIZE + length);
+            let mut rest = length as u32;
+            while rest >= 0x80 {
+                dst.put_u8((rest & 0x7f) as u8 | 0x80);
+                rest >>= 7;
+            }
+            dst.put_u8(rest as u8);
+        },
+    }
+    dst.extend_from_slice(message);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::message::worm_body::WormBody;
+
+    fn join_frame(session_token: u64) -> Vec<u8> {
+        MessageFromClient::ReqJoin { session_token }.make_bytes().unwrap()
+    }
+
+    #[test]
+    fn test_partial_frame() {
+        let mut codec = ServerCodec::new();
+        let frame = join_frame(1234);
+        let mut buffer = BytesMut::new();
+
+        // 한 바이트씩 들어와도 마지막 바이트 전까지는 아무것도 나오지 않아야 한다.
+        for byte in &frame[..frame.len() - 1] {
+            buffer.extend_from_slice(&[*byte]);
+            assert!(codec.decode(&mut buffer).unwrap().is_none());
+        }
+        buffer.extend_from_slice(&frame[frame.len() - 1..]);
+
+        let msg = codec.decode(&mut buffer).unwrap();
+        assert!(matches!(msg, Some(MessageFromClient::ReqJoin { session_token: 1234 })));
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_coalesced_frames() {
+        let mut codec = ServerCodec::new();
+        let mut buffer = BytesMut::new();
+        buffer.extend_from_slice(&join_frame(1));
+        buffer.extend_from_slice(&MessageFromClient::ReqLeave { client_id: 2 }.make_bytes().unwrap());
+        // 세 번째 프레임은 절반만
+        let third = join_frame(3);
+        buffer.extend_from_slice(&third[..3]);
+
+        assert!(matches!(codec.decode(&mut buffer).unwrap(), Some(MessageFromClient::ReqJoin { session_token: 1 })));
+        assert!(matches!(codec.decode(&mut buffer).unwrap(), Some(MessageFromClient::ReqLeave { client_id: 2 })));
+        assert!(codec.decode(&mut buffer).unwrap().is_none());
+        assert_eq!(&buffer[..], &third[..3]);
+    }
+
+    #[test]
+    fn test_max_frame_length() {
+        let worm_body = WormBody::from_parts(7, (1.0, 0.0, 0.0, 1.0), vec![(1.0, 2.0); 8]);
+        let msg = MessageFromServer::ResMove { client_id: 7, worm_body };
+
+        // 보내는 쪽
+        let mut codec = ServerCodec::with_max_frame_length(16);
+        let mut buffer = BytesMut::new();
+        assert!(matches!(codec.encode(&msg, &mut buffer), Err(CodecError::FrameTooLarge { .. })));
+        assert!(buffer.is_empty());
+
+        // 받는 쪽은 길이 필드만 보고 나머지가 오기 전에 거른다.
+        let mut codec = ClientCodec::with_max_frame_length(16);
+        let mut buffer = BytesMut::from(&msg.make_bytes().unwrap()[..4]);
+        assert!(matches!(codec.decode(&mut buffer), Err(CodecError::FrameTooLarge { .. })));
+    }
+
+    // 협상한 형식으로 보내면 몸통이 작아지고, 오차는 고정소수점 한 칸 / 색상 한 단계 안이다.
+    #[test]
+    fn test_negotiated_format_round_trip() {
+        use crate::network::protocol::Capabilities;
+
+        let positions = (0..500).map(|i| (i as f32 * 6.1 - 1500.0, (i as f32 * 0.1).sin() * 2400.0)).collect::<Vec<_>>();
+        let msg = MessageFromServer::ResMove { client_id: 7, worm_body: WormBody::from_parts(7, (0.2, 0.4, 0.6, 1.0), positions.clone()) };
+
+        let format = WireFormat::negotiated(Capabilities::SUPPORTED, 2500.0);
+        let mut server = ServerCodec::new();
+        server.set_format(format);
+        let mut client = ClientCodec::new();
+        client.set_format(format);
+
+        let mut buffer = BytesMut::new();
+        server.encode(&msg, &mut buffer).unwrap();
+        assert!(buffer.len() * 2 < msg.make_bytes().unwrap().len() + 16);
+
+        match client.decode(&mut buffer).unwrap() {
+            Some(MessageFromServer::ResMove { worm_body, .. }) => {
+                for (p, d) in positions.iter().zip(worm_body.positions()) {
+                    assert!((p.0 - d.0).abs() < 0.1 && (p.1 - d.1).abs() < 0.1, "{:?} -> {:?}", p, d);
+                }
+                assert!((worm_body.color().1 - 0.4).abs() <= 0.5 / 255.0 + 1e-6);
+            },
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+
+    // 수천 개 좌표의 몸통도 varint 길이 필드로는 잘리지 않고 오가야 한다.
+    #[test]
+    fn test_varint_frame_length() {
+        let positions = (0..10_000).map(|i| (i as f32, -(i as f32))).collect::<Vec<_>>();
+        let msg = MessageFromServer::ResMove { client_id: 7, worm_body: WormBody::from_parts(7, (1.0, 0.0, 0.0, 1.0), positions.clone()) };
+
+        // u16 길이 필드로는 잘라서 보내지 않고 에러
+        assert!(matches!(msg.make_bytes(), Err(NetworkError::TooLongMsg { .. })));
+        let mut server = ServerCodec::new();
+        let mut buffer = BytesMut::new();
+        assert!(matches!(server.encode(&msg, &mut buffer), Err(CodecError::FrameTooLarge { max_length: 65535, .. })));
+        assert!(buffer.is_empty());
+
+        server.set_frame_length(FrameLength::Varint);
+        server.encode(&msg, &mut buffer).unwrap();
+        let frame = buffer.clone();
+
+        // 한 바이트씩 들어오면 길이 필드도 잘려서 온다
+        let mut client = ClientCodec::new();
+        client.set_frame_length(FrameLength::Varint);
+        let mut buffer = BytesMut::new();
+        for byte in &frame[..4] {
+            buffer.extend_from_slice(&[*byte]);
+            assert!(client.decode(&mut buffer).unwrap().is_none());
+        }
+        buffer.extend_from_slice(&frame[4..]);
+        match client.decode(&mut buffer).unwrap() {
+            Some(MessageFromServer::ResMove { worm_body, .. }) => assert_eq!(worm_body.positions(), &positions[..]),
+            other => panic!("unexpected message: {:?}", other),
+        }
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_varint_length() {
+        let mut codec = ServerCodec::new();
+        codec.set_frame_length(FrameLength::Varint);
+
+        // 5바이트를 넘게 이어지는 길이 필드
+        let mut buffer = BytesMut::from(&[0xff, 0xff, 0xff, 0xff, 0xff, 0x01][..]);
+        assert!(matches!(codec.decode(&mut buffer), Err(CodecError::InvalidFrameLength)));
+
+        // u32 범위를 넘는 길이
+        let mut buffer = BytesMut::from(&[0xff, 0xff, 0xff, 0xff, 0x7f][..]);
+        assert!(matches!(codec.decode(&mut buffer), Err(CodecError::InvalidFrameLength)));
+
+        // 설정한 최대 길이를 넘는 길이는 본문이 오기 전에 거른다
+        let mut buffer = BytesMut::from(&[0x80, 0x80, 0x80, 0x01][..]);
+        assert!(matches!(codec.decode(&mut buffer), Err(CodecError::FrameTooLarge { length: 2_097_152, .. })));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let worm_body = WormBody::from_parts(7, (0.25, 0.5, 0.75, 1.0), vec![(1.0, 2.0), (3.0, 4.0)]);
+        let mut server = ServerCodec::new();
+        let mut client = ClientCodec::new();
+        let mut buffer = BytesMut::new();
+
+        server.encode(MessageFromServer::ResJoin { client_id: 7, worm_body }, &mut buffer).unwrap();
+        match client.decode(&mut buffer).unwrap() {
+            Some(MessageFromServer::ResJoin { client_id, worm_body }) => {
+                assert_eq!(client_id, 7);
+                assert_eq!(worm_body.positions(), &[(1.0, 2.0), (3.0, 4.0)]);
+            },
+            other => panic!("unexpected message: {:?}", other),
+        }
+
+        client.encode(MessageFromClient::ReqEat { client_id: 7, food_id: 3 }, &mut buffer).unwrap();
+        assert!(matches!(server.decode(&mut buffer).unwrap(), Some(MessageFromClient::ReqEat { client_id: 7, food_id: 3 })));
+    }
+}
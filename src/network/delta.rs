@@ -0,0 +1,235 @@
+use crate::network::error::NetworkError;
+use crate::network::message::message_from_server::MessageFromServer;
+use crate::network::message::worm_body::WormBody;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+// 이 횟수만큼 차이만 보냈으면 다음에는 몸통 전체(ResMove)를 다시 보낸다.
+// 차이 계산이 어긋나도 키프레임에서 복구된다.
+pub const KEYFRAME_INTERVAL: u32 = 30;
+
+// 서버 쪽. 연결마다 하나씩 두고, 그 연결에 마지막으로 보낸 몸통과 비교해서
+// `ResMove`를 새 머리 좌표와 길이만 담은 `ResMoveDelta`로 바꾼다.
+// TCP는 순서대로 다 도착하므로 마지막으로 보낸 상태를 클라이언트가 알고 있는 상태로 본다.
+#[derive(Default)]
+pub struct DeltaEncoder {
+    baselines: HashMap<usize, Baseline>,
+}
+
+struct Baseline {
+    positions: Vec<(f32, f32)>,
+    deltas_since_keyframe: u32,
+}
+
+impl DeltaEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 보낼 메세지를 받아서, 차이로 보낼 수 있으면 ResMoveDelta로 바꾸고 아니면 그대로 돌려준다.
+    pub fn encode<'a>(&mut self, msg: &'a MessageFromServer) -> Cow<'a, MessageFromServer> {
+        match msg {
+            MessageFromServer::ResMove { client_id, worm_body } => {
+                let positions = worm_body.positions();
+                if let Some(baseline) = self.baselines.get_mut(client_id)
+                    && baseline.deltas_since_keyframe < KEYFRAME_INTERVAL
+                    && let Some(new_count) = appended_count(&baseline.positions, positions)
+                {
+                    baseline.positions.clear();
+                    baseline.positions.extend_from_slice(positions);
+                    baseline.deltas_since_keyframe += 1;
+                    return Cow::Owned(MessageFromServer::ResMoveDelta {
+                        client_id: *client_id,
+                        length: positions.len(),
+                        new_positions: positions[positions.len() - new_count..].to_vec(),
+                    });
+                }
+                self.keyframe(*client_id, worm_body);
+            },
+            // 새로 생성된 몸통은 이전 몸통과 이어지지 않으므로 다음 차이의 기준으로만 삼는다.
+            MessageFromServer::ResJoin { client_id, worm_body } => self.keyframe(*client_id, worm_body),
+            // 스냅샷을 받은 클라이언트는 모든 몸통을 알고 있으므로 전부 기준으로 삼는다.
+            // 시야에 들어온 지렁이도 마찬가지다.
+            MessageFromServer::ResSnapshot { worm_bodies, .. } | MessageFromServer::ResEnterView { worm_bodies, .. } => {
+                for worm_body in worm_bodies {
+                    self.keyframe(worm_body.client_id(), worm_body);
+                }
+            },
+            MessageFromServer::ResLeave { client_id } | MessageFromServer::ResDie { client_id } => {
+                self.baselines.remove(client_id);
+            },
+            MessageFromServer::ResLeaveView { client_ids, .. } => {
+                for client_id in client_ids {
+                    self.baselines.remove(client_id);
+                }
+            },
+            _ => {},
+        }
+        Cow::Borrowed(msg)
+    }
+
+    fn keyframe(&mut self, client_id: usize, worm_body: &WormBody) {
+        self.baselines.insert(client_id, Baseline {
+            positions: worm_body.positions().to_vec(),
+            deltas_since_keyframe: 0,
+        });
+    }
+}
+
+// 새 몸통이 "이전 몸통의 뒷부분 + 새로 붙은 머리 좌표들"이면 새로 붙은 좌표 수를 돌려준다.
+// 몸통은 꼬리 -> 머리 순서이고, 이동하면 뒤에 좌표가 붙고 길이를 넘는 만큼 앞에서 빠진다.
+fn appended_count(previous: &[(f32, f32)], current: &[(f32, f32)]) -> Option<usize> {
+    let previous_head = previous.last()?;
+    // 한 tick에 붙는 좌표는 많지 않으므로 머리 쪽부터 찾는다.
+    let head_index = current.iter().rposition(|p| p == previous_head)?;
+    let kept = &current[..=head_index];
+    if kept.len() > previous.len() || previous[previous.len() - kept.len()..] != *kept {
+        return None;
+    }
+    Some(current.len() - kept.len())
+}
+
+// 클라이언트 쪽. 받은 몸통을 기억해두었다가 `ResMoveDelta`를 다시 전체 몸통의 `ResMove`로 되돌린다.
+// 게임 로직은 항상 `ResMove`만 보면 된다.
+#[derive(Default)]
+pub struct DeltaDecoder {
+    bodies: HashMap<usize, WormBody>,
+}
+
+impl DeltaDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn decode(&mut self, msg: MessageFromServer) -> Result<MessageFromServer, NetworkError> {
+        match msg {
+            MessageFromServer::ResMoveDelta { client_id, length, new_positions } => {
+                let Some(baseline) = self.bodies.get(&client_id) else {
+                    return Err(NetworkError::MissingBaseline { client_id });
+                };
+
+                let mut positions = baseline.positions().to_vec();
+                positions.extend(new_positions);
+                // 이전 몸통보다 길어질 수는 있어도, 가진 좌표보다 길 수는 없다.
+                if length > positions.len() {
+                    return Err(NetworkError::DeltaTooLong { client_id, length, known_length: positions.len() });
+                }
+                positions.drain(..positions.len() - length);
+
+                let worm_body = WormBody::from_parts(client_id, baseline.color(), positions);
+                self.bodies.insert(client_id, worm_body.clone());
+                Ok(MessageFromServer::ResMove { client_id, worm_body })
+            },
+            MessageFromServer::ResMove { client_id, ref worm_body } | MessageFromServer::ResJoin { client_id, ref worm_body } => {
+                self.bodies.insert(client_id, worm_body.clone());
+                Ok(msg)
+            },
+            MessageFromServer::ResSnapshot { ref worm_bodies, .. } => {
+                self.bodies = worm_bodies.iter().map(|worm_body| (worm_body.client_id(), worm_body.clone())).collect();
+                Ok(msg)
+            },
+            MessageFromServer::ResEnterView { ref worm_bodies, .. } => {
+                for worm_body in worm_bodies {
+                    self.bodies.insert(worm_body.client_id(), worm_body.clone());
+                }
+                Ok(msg)
+            },
+            MessageFromServer::ResLeave { client_id } | MessageFromServer::ResDie { client_id } => {
+                self.bodies.remove(&client_id);
+                Ok(msg)
+            },
+            MessageFromServer::ResLeaveView { ref client_ids, .. } => {
+                for client_id in client_ids {
+                    self.bodies.remove(client_id);
+                }
+                Ok(msg)
+            },
+            msg => Ok(msg),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLOR: (f32, f32, f32, f32) = (0.5, 0.25, 1.0, 1.0);
+
+    fn body(range: std::ops::Range<usize>) -> WormBody {
+        WormBody::from_parts(7, COLOR, range.map(|i| (i as f32, -(i as f32))).collect())
+    }
+
+    // 인코더를 거친 메세지를 바이트로 바꿨다가 디코더로 되돌린다.
+    fn send(encoder: &mut DeltaEncoder, decoder: &mut DeltaDecoder, msg: MessageFromServer) -> (bool, MessageFromServer) {
+        let encoded = encoder.encode(&msg);
+        let is_delta = matches!(*encoded, MessageFromServer::ResMoveDelta { .. });
+        let bytes = encoded.make_bytes().unwrap();
+        let received = MessageFromServer::new(&bytes[2..]).unwrap();
+        (is_delta, decoder.decode(received).unwrap())
+    }
+
+    fn positions(msg: &MessageFromServer) -> Vec<(f32, f32)> {
+        match msg {
+            MessageFromServer::ResMove { worm_body, .. } => worm_body.positions().to_vec(),
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_delta_round_trip() {
+        let mut encoder = DeltaEncoder::new();
+        let mut decoder = DeltaDecoder::new();
+
+        // 처음은 전체
+        let (is_delta, _) = send(&mut encoder, &mut decoder, MessageFromServer::ResJoin { client_id: 7, worm_body: body(0..16) });
+        assert!(!is_delta);
+
+        // 이동: 머리 2개가 붙고 꼬리 2개가 빠짐, 성장: 머리만 붙음, 데미지: 꼬리만 빠짐, 정지: 그대로
+        for next in [body(2..18), body(2..20), body(5..20), body(5..20)] {
+            let expected = next.positions().to_vec();
+            let (is_delta, received) = send(&mut encoder, &mut decoder, MessageFromServer::ResMove { client_id: 7, worm_body: next });
+            assert!(is_delta);
+            assert_eq!(positions(&received), expected);
+            if let MessageFromServer::ResMove { worm_body, .. } = received {
+                assert_eq!(worm_body.color(), COLOR);
+            }
+        }
+
+        // 이어지지 않는 몸통 (리스폰 등)은 전체로 보낸다
+        let (is_delta, received) = send(&mut encoder, &mut decoder, MessageFromServer::ResMove { client_id: 7, worm_body: body(100..116) });
+        assert!(!is_delta);
+        assert_eq!(positions(&received), body(100..116).positions());
+    }
+
+    #[test]
+    fn test_periodic_keyframe() {
+        let mut encoder = DeltaEncoder::new();
+        let mut decoder = DeltaDecoder::new();
+        send(&mut encoder, &mut decoder, MessageFromServer::ResMove { client_id: 7, worm_body: body(0..16) });
+
+        let mut keyframes = 0;
+        for i in 1..=(KEYFRAME_INTERVAL as usize + 1) * 2 {
+            let (is_delta, _) = send(&mut encoder, &mut decoder, MessageFromServer::ResMove { client_id: 7, worm_body: body(i..i + 16) });
+            if !is_delta {
+                keyframes += 1;
+            }
+        }
+        assert_eq!(keyframes, 2);
+    }
+
+    #[test]
+    fn test_missing_baseline() {
+        let mut decoder = DeltaDecoder::new();
+        let msg = MessageFromServer::ResMoveDelta { client_id: 3, length: 1, new_positions: vec![(1.0, 1.0)] };
+        assert!(matches!(decoder.decode(msg), Err(NetworkError::MissingBaseline { client_id: 3 })));
+    }
+
+    #[test]
+    fn test_delta_too_long() {
+        let mut decoder = DeltaDecoder::new();
+        decoder.decode(MessageFromServer::ResMove { client_id: 3, worm_body: body(0..4) }).unwrap();
+
+        let msg = MessageFromServer::ResMoveDelta { client_id: 3, length: 6, new_positions: vec![(1.0, 1.0)] };
+        assert!(matches!(decoder.decode(msg), Err(NetworkError::DeltaTooLong { client_id: 3, length: 6, known_length: 5 })));
+    }
+}
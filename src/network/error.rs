@@ -6,6 +6,15 @@ pub enum ProtocolError {
 
     #[error(transparent)]
     Rule(#[from] RuleError),
+
+    // 어느 메세지의 어느 필드를 몇 번째 바이트에서 읽다가 실패했는지
+    #[error("Failed to decode {message}.{field} at byte {offset}. {source}")]
+    Decode {
+        message: &'static str,
+        field: &'static str,
+        offset: usize,
+        source: NetworkError,
+    },
 }
 
 #[derive(Error, Debug)]
@@ -22,6 +31,43 @@ pub enum NetworkError {
 
     #[error("Message bytes are not valid for the operation. (message length: {input_length}")]
     InvalidMsg { input_length: usize },
+
+    // 길이 필드에 담을 수 없을 만큼 긴 메세지. 잘라서 보내면 스트림이 깨지므로 보내지 않는다.
+    #[error("Message is too long for the length field. (length: {length}, max length: {max_length})")]
+    TooLongMsg { length: usize, max_length: usize },
+
+    // 메세지를 다 읽었는데 바이트가 남은 경우
+    #[error("Message has trailing bytes. (remaining length: {remaining_length})")]
+    TrailingBytes { remaining_length: usize },
+
+    // 이전 몸통을 모르는데 차이만 온 경우. 다음 키프레임까지 기다려야 한다.
+    #[error("No baseline body for delta. (client id: {client_id})")]
+    MissingBaseline { client_id: usize },
+
+    // 차이의 몸 길이가 이전 몸통과 새 좌표를 합친 것보다 긴 경우. 이것도 다음 키프레임까지 기다린다.
+    #[error("Delta length is longer than the known body. (client id: {client_id}, length: {length}, known length: {known_length})")]
+    DeltaTooLong { client_id: usize, length: usize, known_length: usize },
+
+    // NaN, inf 좌표는 월드 계산을 망가뜨리므로 받지 않는다
+    #[error("Float value is not finite. ({0})")]
+    NotFinite(f32),
+}
+
+// 스트림을 메세지 단위로 자르거나 메세지를 스트림에 쓸 때 발생
+#[derive(Error, Debug)]
+pub enum CodecError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Protocol(#[from] ProtocolError),
+
+    #[error("Frame is too large. (length: {length}, max length: {max_length})")]
+    FrameTooLarge { length: usize, max_length: usize },
+
+    // varint 길이 필드가 u32 범위를 넘는 경우
+    #[error("Frame length field is malformed.")]
+    InvalidFrameLength,
 }
 
 // 올바르지 않은 유형의 메세지가 들어왔을 때 발생
@@ -0,0 +1,103 @@
+use std::time::{Duration, Instant};
+
+// 이 주기로 상대에게 ping을 보낸다
+pub const PING_INTERVAL: Duration = Duration::from_secs(1);
+// 서버는 이 시간 동안 아무 메세지도 받지 못한 연결을 끊는다
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// RTT 평활 계수 (RFC 6298과 같은 값)
+const RTT_ALPHA: f64 = 1.0 / 8.0;
+const JITTER_BETA: f64 = 1.0 / 4.0;
+
+// 연결마다 하나씩 두고 ping에 넣을 시각을 만들고, 돌아온 pong으로 RTT와 지터를 추정한다.
+// 시각은 이 값을 만든 뒤로 지난 us라서 상대 시계와 맞출 필요가 없다.
+pub struct Heartbeat {
+    started: Instant,
+    rtt: Option<f64>,   // 초 단위. 첫 pong 전에는 None
+    jitter: f64,
+}
+
+impl Heartbeat {
+    pub fn new() -> Self {
+        Self { started: Instant::now(), rtt: None, jitter: 0.0 }
+    }
+
+    // ping에 담을 지금 시각
+    pub fn timestamp(&self) -> u64 {
+        self.started.elapsed().as_micros() as u64
+    }
+
+    // 내가 보낸 ping의 시각이 pong으로 돌아왔을 때. 이번 왕복 시간을 돌려준다.
+    // 아직 오지 않은 시각(조작된 pong)이면 무시한다.
+    pub fn on_pong(&mut self, timestamp: u64) -> Option<Duration> {
+        let now = self.timestamp();
+        if timestamp > now {
+            return None;
+        }
+        let sample = Duration::from_micros(now - timestamp);
+        self.add_sample(sample);
+        Some(sample)
+    }
+
+    // 평활 RTT는 지수 이동 평균, 지터는 평균과의 차이의 이동 평균
+    pub fn add_sample(&mut self, sample: Duration) {
+        let sample = sample.as_secs_f64();
+        match self.rtt {
+            None => {
+                self.rtt = Some(sample);
+                self.jitter = sample / 2.0;
+            },
+            Some(rtt) => {
+                self.jitter = (1.0 - JITTER_BETA) * self.jitter + JITTER_BETA * (rtt - sample).abs();
+                self.rtt = Some((1.0 - RTT_ALPHA) * rtt + RTT_ALPHA * sample);
+            },
+        }
+    }
+
+    pub fn rtt(&self) -> Option<Duration> {
+        self.rtt.map(Duration::from_secs_f64)
+    }
+
+    pub fn jitter(&self) -> Duration {
+        Duration::from_secs_f64(self.jitter)
+    }
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rtt_smoothing() {
+        let mut heartbeat = Heartbeat::new();
+        assert_eq!(heartbeat.rtt(), None);
+
+        heartbeat.add_sample(Duration::from_millis(100));
+        assert_eq!(heartbeat.rtt(), Some(Duration::from_millis(100)));
+        assert_eq!(heartbeat.jitter(), Duration::from_millis(50));
+
+        // 튀는 값 하나로 크게 흔들리지 않는다
+        heartbeat.add_sample(Duration::from_millis(500));
+        assert_eq!(heartbeat.rtt(), Some(Duration::from_millis(150)));
+        assert_eq!(heartbeat.jitter(), Duration::from_micros(137_500));
+
+        // 일정하면 지터는 0으로 줄어든다
+        for _ in 0..200 {
+            heartbeat.add_sample(Duration::from_millis(150));
+        }
+        assert!(heartbeat.jitter() < Duration::from_micros(10));
+    }
+
+    #[test]
+    fn test_future_pong_ignored() {
+        let mut heartbeat = Heartbeat::new();
+        assert!(heartbeat.on_pong(u64::MAX).is_none());
+        assert!(heartbeat.on_pong(heartbeat.timestamp()).is_some());
+    }
+}
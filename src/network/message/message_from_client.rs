@@ -1,5 +1,7 @@
-use crate::network::error::ProtocolError;
+use crate::network::error::{NetworkError, ProtocolError};
 use crate::network::{error, util};
+use crate::network::message::reader::MessageReader;
+use crate::network::protocol::{Capabilities, WireFormat};
 use crate::network::message::worm_body::WormBody;
 use crate::network::util::u16_be_to_bytes;
 
@@ -8,16 +10,42 @@ use crate::network::util::u16_be_to_bytes;
 #[derive(Debug)]
 pub enum MessageFromClient {
     // 1XX
-    // 길이(2bytes)  |   유형(1byte)   |   메세지(N bytes)
+    // 길이(u16 또는 varint)  |   유형(1byte)   |   메세지(N bytes)
+    // 아래 길이는 유형 + 메세지의 바이트 수
 
-    //      3       |       101     |   client id(u16)
-    ReqJoin {
-        client_id: usize,
+    //      9       |       101     |   세션 토큰(u64)
+    ReqJoin {                   // id는 서버가 정해서 ResSession으로 알려준다
+        session_token: u64,     // 처음 참가하면 0, 재접속이면 이전에 받은 토큰으로 같은 id를 되찾는다
     },
     //      3       |       102     |   client id(u16)
     ReqLeave {
         client_id: usize,
     },
+    //      7       |       103     |   프로토콜 버전(u16), 지원 기능(u32)
+    ReqHello {                  // 접속하자마자 ReqJoin보다 먼저 보낸다
+        version: u16,
+        capabilities: Capabilities,
+    },
+    //      9       |       104     |   보낸 시각(u64)
+    ReqPing {                   // 서버는 시각을 그대로 담아 ResPong으로 돌려준다
+        timestamp: u64,         // 보낸 쪽 시계 기준 (us). 받는 쪽은 해석하지 않는다
+    },
+    //      9       |       105     |   ResPing의 시각(u64)
+    ReqPong {
+        timestamp: u64,
+    },
+    //      1       |       111     |   (없음)
+    ReqRoomList,                // 서버는 지금 열려있는 방들을 ResRoomList로 알려준다
+    //      7 + N   |       112     |   맵 반지름(f32), 최대 인원(u16), 방 이름(UTF-8, N bytes)
+    ReqCreateRoom {             // 새 방을 만들고 바로 들어간다. 결과는 ResRoomEntered 또는 ResRoomError
+        map_radius: f32,
+        max_players: usize,
+        name: String,
+    },
+    //      1 + N   |       113     |   방 이름(UTF-8, N bytes)
+    ReqEnterRoom {              // 이미 있는 방으로 옮긴다. 들어간 뒤 ReqJoin을 보내야 지렁이가 생긴다
+        name: String,
+    },
 
     // 2XX
     //      3 + N   |       201     |   client id(u16), 지렁이 몸통 정보(N bytes)
@@ -25,95 +53,221 @@ pub enum MessageFromClient {
         client_id: usize,
         worm_body: WormBody,    // 각 클라이언트는 자기 위치 움직일 때, 자신의 몸통 좌표들을 전송
     },
-    //      5       |       202     |   client id(u16), 먹이(u16)
-    ReqEat {
+    //      7       |       202     |   client id(u16), 먹이 id(u32)
+    ReqEat {                    // 서버는 먹이가 실제로 머리 근처에 있는지 확인하고 ResEat으로 결과를 알려준다
         client_id: usize,
-        food_amount: usize, // 먹이의 크기
+        food_id: u32,
     },
     //      3       |       203     |   client id(u16)
     ReqDie {
         client_id: usize,
     },
+    //      16      |       204     |   client id(u16), 입력 번호(u32), 목표 방향(f32, f32), 부스트 여부(u8)
+    ReqInput {
+        client_id: usize,
+        seq: u32,               // 서버가 어느 입력까지 반영했는지 돌려주기 위한 일련번호
+        target_dir: (f32, f32), // 서버가 지렁이를 움직이므로 클라이언트는 입력만 보낸다
+        boost: bool,
+    },
 }
 
 impl MessageFromClient {
 
     // 검열된 바이트 배열을 가지고, 클라이언트 요청 구조체를 생성
+    // 어떤 바이트가 들어와도 패닉하지 않고, 실패한 필드와 위치를 에러로 돌려준다.
     pub fn new(message_bytes: &[u8]) -> Result<Self, ProtocolError> {
-        // 패킷 유형
-        let type_num = message_bytes[0] as usize;
+        Self::new_with(message_bytes, &WireFormat::FULL)
+    }
 
-        // 해당 패킷 유형의 내용물
-        let message_body_bytes = &message_bytes[1..];
+    // 연결마다 협상된 형식으로 몸통을 읽는다
+    pub fn new_with(message_bytes: &[u8], format: &WireFormat) -> Result<Self, ProtocolError> {
+        let mut reader = MessageReader::new(message_bytes);
 
-        match type_num {
+        // 패킷 유형
+        let type_num = reader.u8("type")? as usize;
+
+        let msg = match type_num {
             101 => {
-                let client_id = util::bytes_to_u16_be(&message_body_bytes[..2])? as usize;
-                Ok(MessageFromClient::ReqJoin { client_id })
+                reader.set_message("ReqJoin");
+                let session_token = reader.u64("session_token")?;
+                MessageFromClient::ReqJoin { session_token }
             },
             102 => {
-                let client_id = util::bytes_to_u16_be(&message_body_bytes[..2])? as usize;
-                Ok(MessageFromClient::ReqLeave { client_id })
+                reader.set_message("ReqLeave");
+                let client_id = reader.u16("client_id")? as usize;
+                MessageFromClient::ReqLeave { client_id }
+            },
+            103 => {
+                reader.set_message("ReqHello");
+                let version = reader.u16("version")?;
+                let capabilities = Capabilities::from_bits(reader.u32("capabilities")?);
+                MessageFromClient::ReqHello { version, capabilities }
+            },
+            104 => {
+                reader.set_message("ReqPing");
+                let timestamp = reader.u64("timestamp")?;
+                MessageFromClient::ReqPing { timestamp }
+            },
+            105 => {
+                reader.set_message("ReqPong");
+                let timestamp = reader.u64("timestamp")?;
+                MessageFromClient::ReqPong { timestamp }
+            },
+            111 => MessageFromClient::ReqRoomList,
+            112 => {
+                reader.set_message("ReqCreateRoom");
+                let map_radius = reader.f32("map_radius")?;
+                let max_players = reader.u16("max_players")? as usize;
+                let name = reader.string("name")?;
+                MessageFromClient::ReqCreateRoom { map_radius, max_players, name }
+            },
+            113 => {
+                reader.set_message("ReqEnterRoom");
+                let name = reader.string("name")?;
+                MessageFromClient::ReqEnterRoom { name }
             },
             201 => {
-                let client_id = util::bytes_to_u16_be(&message_body_bytes[..2])? as usize;
-                let worm_body = WormBody::new(client_id, &message_body_bytes[2..])?;
-                Ok(MessageFromClient::ReqMove { client_id, worm_body })
+                reader.set_message("ReqMove");
+                let worm_body = reader.worm_body(format)?;
+                MessageFromClient::ReqMove { client_id: worm_body.client_id(), worm_body }
             },
             202 => {
-                let client_id = util::bytes_to_u16_be(&message_body_bytes[..2])? as usize;
-                let food_amount = util::bytes_to_u16_be(&message_body_bytes[2..])? as usize;
-                Ok(MessageFromClient::ReqEat { client_id, food_amount })
+                reader.set_message("ReqEat");
+                let client_id = reader.u16("client_id")? as usize;
+                let food_id = reader.u32("food_id")?;
+                MessageFromClient::ReqEat { client_id, food_id }
             },
             203 => {
-                let client_id = util::bytes_to_u16_be(&message_body_bytes[..2])? as usize;
-                Ok(MessageFromClient::ReqDie { client_id })
+                reader.set_message("ReqDie");
+                let client_id = reader.u16("client_id")? as usize;
+                MessageFromClient::ReqDie { client_id }
+            },
+            204 => {
+                reader.set_message("ReqInput");
+                let client_id = reader.u16("client_id")? as usize;
+                let seq = reader.u32("seq")?;
+                let target_dir = reader.pair("target_dir")?;
+                let boost = reader.bool("boost")?;
+                MessageFromClient::ReqInput { client_id, seq, target_dir, boost }
             },
-            n => Err(ProtocolError::from(error::RuleError::InvalidPacketType(n))),
+            n => return Err(ProtocolError::from(error::RuleError::InvalidPacketType(n))),
+        };
+        reader.finish()?;
+
+        Ok(msg)
+    }
+
+    // 메세지가 누구 지렁이에 대한 것인지. 서버는 이 값이 보낸 연결의 id와 같은지 확인한다.
+    pub fn client_id(&self) -> Option<usize> {
+        match *self {
+            MessageFromClient::ReqJoin { .. }
+            | MessageFromClient::ReqHello { .. }
+            | MessageFromClient::ReqPing { .. }
+            | MessageFromClient::ReqPong { .. }
+            | MessageFromClient::ReqRoomList
+            | MessageFromClient::ReqCreateRoom { .. }
+            | MessageFromClient::ReqEnterRoom { .. } => None,
+            MessageFromClient::ReqLeave { client_id }
+            | MessageFromClient::ReqMove { client_id, .. }
+            | MessageFromClient::ReqEat { client_id, .. }
+            | MessageFromClient::ReqDie { client_id }
+            | MessageFromClient::ReqInput { client_id, .. } => Some(client_id),
         }
     }
 
-    pub fn make_bytes(&self) -> Vec<u8> {
+    // 길이(u16) 필드까지 붙인 프레임. 테스트처럼 코덱 없이 바로 소켓에 쓸 때 쓴다.
+    // 길이 필드에 담을 수 없는 메세지는 잘라내지 않고 에러를 낸다.
+    pub fn make_bytes(&self) -> Result<Vec<u8>, NetworkError> {
+        let message_bytes = self.make_message_bytes_with(&WireFormat::FULL);
+        let length = u16::try_from(message_bytes.len())
+            .map_err(|_| NetworkError::TooLongMsg { length: message_bytes.len(), max_length: u16::MAX as usize })?;
+
+        let mut packet = Vec::with_capacity(2 + message_bytes.len());
+        packet.extend(u16_be_to_bytes(length));
+        packet.extend(message_bytes);
+        Ok(packet)
+    }
+
+    // 길이 필드를 뺀 유형 + 메세지. 길이 필드는 연결마다 협상된 형식으로 코덱이 붙인다.
+    pub fn make_message_bytes_with(&self, format: &WireFormat) -> Vec<u8> {
         match *self {
-            MessageFromClient::ReqJoin { client_id } => {
-                let mut packet = Vec::with_capacity(5);
-                packet.extend(u16_be_to_bytes(3));
+            MessageFromClient::ReqJoin { session_token } => {
+                let mut packet = Vec::with_capacity(9);
                 packet.push(101u8);
-                packet.extend(u16_be_to_bytes(client_id as u16));
+                packet.extend(session_token.to_be_bytes());
                 packet
             },
             MessageFromClient::ReqLeave { client_id } => {
-                let mut packet = Vec::with_capacity(5);
-                packet.extend(u16_be_to_bytes(3));
+                let mut packet = Vec::with_capacity(3);
                 packet.push(102u8);
                 packet.extend(u16_be_to_bytes(client_id as u16));
                 packet
             },
-            MessageFromClient::ReqMove { client_id, ref worm_body } => {
-                let worm_body_bytes = worm_body.make_bytes();
+            MessageFromClient::ReqHello { version, capabilities } => {
+                let mut packet = Vec::with_capacity(7);
+                packet.push(103u8);
+                packet.extend(u16_be_to_bytes(version));
+                packet.extend(util::u32_be_to_bytes(capabilities.bits()));
+                packet
+            },
+            MessageFromClient::ReqPing { timestamp } => {
+                let mut packet = Vec::with_capacity(9);
+                packet.push(104u8);
+                packet.extend(timestamp.to_be_bytes());
+                packet
+            },
+            MessageFromClient::ReqPong { timestamp } => {
+                let mut packet = Vec::with_capacity(9);
+                packet.push(105u8);
+                packet.extend(timestamp.to_be_bytes());
+                packet
+            },
+            MessageFromClient::ReqRoomList => vec![111u8],
+            MessageFromClient::ReqCreateRoom { map_radius, max_players, ref name } => {
+                let mut packet = Vec::with_capacity(7 + name.len());
+                packet.push(112u8);
+                packet.extend(map_radius.to_be_bytes());
+                packet.extend(u16_be_to_bytes(max_players as u16));
+                packet.extend(name.as_bytes());
+                packet
+            },
+            MessageFromClient::ReqEnterRoom { ref name } => {
+                let mut packet = Vec::with_capacity(1 + name.len());
+                packet.push(113u8);
+                packet.extend(name.as_bytes());
+                packet
+            },
+            MessageFromClient::ReqMove { client_id: _, ref worm_body } => {
+                let worm_body_bytes = worm_body.make_bytes_with(format);
 
                 // message type length (1 bytes) + client id (2 bytes) + worm positions (N bytes)
-                let mut packet = Vec::with_capacity(5 + worm_body_bytes.len());
-                packet.extend(u16_be_to_bytes(1 + worm_body_bytes.len() as u16));
+                let mut packet = Vec::with_capacity(3 + worm_body_bytes.len());
                 packet.push(201u8);
                 packet.extend(worm_body_bytes);
                 packet
             },
-            MessageFromClient::ReqEat { client_id, food_amount } => {
+            MessageFromClient::ReqEat { client_id, food_id } => {
                 let mut packet = Vec::with_capacity(7);
-                packet.extend(u16_be_to_bytes(5));
                 packet.push(202u8);
                 packet.extend(u16_be_to_bytes(client_id as u16));
-                packet.extend(u16_be_to_bytes(food_amount as u16));
+                packet.extend(util::u32_be_to_bytes(food_id));
                 packet
             },
             MessageFromClient::ReqDie { client_id } => {
-                let mut packet = Vec::with_capacity(5);
-                packet.extend(u16_be_to_bytes(3));
+                let mut packet = Vec::with_capacity(3);
                 packet.push(203u8);
                 packet.extend(u16_be_to_bytes(client_id as u16));
                 packet
             },
+            MessageFromClient::ReqInput { client_id, seq, target_dir, boost } => {
+                let mut packet = Vec::with_capacity(16);
+                packet.push(204u8);
+                packet.extend(u16_be_to_bytes(client_id as u16));
+                packet.extend(util::u32_be_to_bytes(seq));
+                packet.extend(util::positions_to_bytes(&[target_dir]));
+                packet.push(if boost { 1 } else { 0 });
+                packet
+            },
         }
     }
 }
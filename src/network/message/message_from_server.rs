@@ -1,14 +1,19 @@
-use crate::network::error::ProtocolError;
+use crate::network::error::{NetworkError, ProtocolError};
+use crate::network::message::reader::MessageReader;
+use crate::network::message::room::RoomInfo;
+use crate::network::message::snapshot::{FoodInfo, ZoneInfo};
 use crate::network::message::worm_body::WormBody;
+use crate::network::protocol::{Capabilities, WireFormat};
 use crate::network::{error, util};
 use crate::network::util::u16_be_to_bytes;
 
 // Req*는 Client -> Server 요청,
 // Res*는 Server -> Client 응답.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum MessageFromServer {
     // 1XX
-    // 길이(2bytes)  |   유형(1byte)   |   메세지(N bytes)
+    // 길이(u16 또는 varint)  |   유형(1byte)   |   메세지(N bytes)
+    // 아래 길이는 유형 + 메세지의 바이트 수
 
     //      3 + N   |       101     |   client id(u16), worm_body(N bytes)
     ResJoin {
@@ -19,6 +24,48 @@ pub enum MessageFromServer {
     ResLeave {
         client_id: usize,       // 클라 나갈 때, 그대로 다른 클라들에게 전부 echo
     },
+    //      11      |       103     |   프로토콜 버전(u16), 협상된 기능(u32), 서버 초당 tick 수(f32)
+    ResWelcome {                // ReqHello를 받아준 경우. 이후 이 연결은 협상된 기능만 쓴다.
+        version: u16,
+        capabilities: Capabilities,
+        tick_rate: f32,         // 클라이언트는 이 간격으로 예측하고 입력을 보낸다
+    },
+    //      3 + N   |       104     |   서버 프로토콜 버전(u16), 거절 사유(UTF-8, N bytes)
+    ResReject {                 // 접속을 받아줄 수 없거나 게임 중에 내보내는 경우. 보낸 뒤 서버가 연결을 끊는다.
+        version: u16,
+        reason: String,
+    },
+    //      11      |       105     |   client id(u16), 세션 토큰(u64)
+    ResSession {                // 참가한 본인에게만 보낸다. 토큰은 다른 클라이언트가 알면 안 된다.
+        client_id: usize,
+        session_token: u64,
+    },
+    //      9       |       106     |   보낸 시각(u64)
+    ResPing {                   // 클라이언트는 시각을 그대로 담아 ReqPong으로 돌려준다
+        timestamp: u64,         // 서버 시계 기준 (us)
+    },
+    //      9       |       107     |   ReqPing의 시각(u64)
+    ResPong {
+        timestamp: u64,
+    },
+    //      6 + N   |       108     |   재시작 예정 여부(u8), 재시작까지 남은 시간(u32, 초), 종료 사유(UTF-8, N bytes)
+    ResShutdown {               // 서버가 종료 중이다. 보낸 뒤 남은 메세지를 마저 보내고 연결을 끊는다.
+        restart_in_secs: Option<u32>,   // 다시 켜질 예정이면 대략 몇 초 뒤인지
+        reason: String,
+    },
+    //      3 + N   |       111     |   방 수(u16), (이름 길이(u8), 이름(UTF-8), 인원(u16), 최대 인원(u16), 맵 반지름(f32)) 반복
+    ResRoomList {               // ReqRoomList를 보낸 클라이언트에게만
+        rooms: Vec<RoomInfo>,
+    },
+    //      5 + N   |       112     |   맵 반지름(f32), 방 이름(UTF-8, N bytes)
+    ResRoomEntered {            // 방을 만들었거나 옮긴 경우. 이전 방의 지렁이는 지워졌으므로 ReqJoin으로 다시 참가한다.
+        map_radius: f32,
+        name: String,
+    },
+    //      1 + N   |       113     |   실패 사유(UTF-8, N bytes)
+    ResRoomError {              // 방을 만들거나 옮기지 못한 경우. 클라이언트는 원래 방에 그대로 있다.
+        reason: String,
+    },
 
     // 2XX
     //      3 + N   |       201     |   client id(u16), 지렁이 몸통 정보(N bytes)
@@ -26,101 +73,520 @@ pub enum MessageFromServer {
         client_id: usize,
         worm_body: WormBody,    // 서버는 클라에게 받은 내용을 그대로 다른 클라들에게 echo
     },
-    //      6       |       202     |   client id(u16), 먹이(u16), 성공여부(u8)
-    ResEat {
+    //      10      |       202     |   client id(u16), 먹이 id(u32), 먹이(u16), 성공여부(u8)
+    ResEat {                    // ReqEat을 보낸 클라이언트에게만. 먹은 먹이는 ResFoodRemove로 모두에게 알려준다.
         client_id: usize,
-        food_amount: usize,
+        food_id: u32,
+        food_amount: usize,     // 늘어난 몸 길이. 실패하면 0
         is_ok: bool,            // 성공 여부 판단
     },
     //      3       |       203     |   client id(u16)
     ResDie {                    // 죽은 클라 정보를 모든 클라에게 echo
         client_id: usize,
     },
+    //      32      |       205     |   client id(u16), 입력 번호(u32), 머리(f32, f32), 방향(f32, f32), 남은 부스트(f32),
+    //                                  부스트 가능(u8), 최대 길이(u32)
+    ResState {                  // 지렁이 주인에게만 보내는 서버 상태. 클라이언트는 이 상태로 되감은 뒤 남은 입력을 다시 적용한다.
+        client_id: usize,       // 몸통은 같은 tick에 먼저 보낸 ResMove(ResMoveDelta)로 받는다
+        input_seq: u32,         // 이 상태에 마지막으로 반영된 입력 번호
+        head: (f32, f32),
+        dir: (f32, f32),
+        boost_remaining: f32,
+        boost_available: bool,
+        max_points: usize,
+    },
+    //      7 + N   |       206     |   client id(u16), 몸 길이(u32), 새 머리 좌표들(N bytes)
+    ResMoveDelta {              // DELTA_BODIES를 협상한 연결에만 보낸다. 이전 몸통에 새 좌표를 붙이고 앞쪽을 길이만큼 남긴다.
+        client_id: usize,
+        length: usize,
+        new_positions: Vec<(f32, f32)>,
+    },
+    //      13 + N  |       207     |   맵 반지름(f32),
+    //                                  영역 수(u16), (중심(f32, f32), 반지름(f32), 초당 데미지(f32)) 반복,
+    //                                  지렁이 수(u16), (몸통 길이(u32), 지렁이 몸통 정보) 반복,
+    //                                  먹이 수(u32), (먹이 id(u32), 좌표(f32, f32), 성장(u8)) 반복
+    ResSnapshot {               // ResJoin 뒤에 참가한 본인에게만 보내는 맵과 시야 안의 지렁이, 먹이. 클라이언트는 이걸로 맵을 새로 그린다. u16 프레임에 다 들어가지 않으면 나머지는 바로 뒤에 ResEnterView로 온다.
+        map_radius: f32,
+        damage_zones: Vec<ZoneInfo>,
+        worm_bodies: Vec<WormBody>,
+        foods: Vec<FoodInfo>,
+    },
+    //      1 + N   |       208     |   (먹이 id(u32), 좌표(f32, f32), 성장(u8)) 반복
+    ResFoodSpawn {              // tick마다 새로 생긴 먹이 중 그 클라이언트의 시야 안에 있는 것을 모아서 보낸다
+        foods: Vec<FoodInfo>,
+    },
+    //      1 + N   |       209     |   먹이 id(u32) 반복
+    ResFoodRemove {             // tick마다 먹혀서 없어진 먹이 중 그 클라이언트가 알고 있던 것을 모아서 보낸다
+        food_ids: Vec<u32>,
+    },
+    //      5       |       210     |   죽인 client id(u16), 죽은 client id(u16)
+    ResKill {                   // 서버가 충돌로 판정한 죽음. 같은 tick의 ResDie와 함께 모두에게 보낸다.
+        killer_id: usize,       // 머리가 부딪힌 몸통의 주인
+        victim_id: usize,
+    },
+    //      7 + N   |       211     |   지렁이 수(u16), (몸통 길이(u32), 지렁이 몸통 정보) 반복,
+    //                                  먹이 수(u32), (먹이 id(u32), 좌표(f32, f32), 성장(u8)) 반복
+    ResEnterView {              // 시야에 새로 들어온 지렁이와 먹이. 클라이언트마다 따로 보낸다.
+        worm_bodies: Vec<WormBody>,
+        foods: Vec<FoodInfo>,
+    },
+    //      3 + N   |       212     |   지렁이 수(u16), client id(u16) 반복, 먹이 id(u32) 반복
+    ResLeaveView {              // 시야 밖으로 나가서 더 이상 보내지 않는 지렁이와 먹이. 클라이언트는 지우기만 한다.
+        client_ids: Vec<usize>,
+        food_ids: Vec<u32>,
+    },
 }
 
 impl MessageFromServer {
 
     // 검열된 바이트 배열을 가지고, 서버 응답 구조체를 생성
+    // 어떤 바이트가 들어와도 패닉하지 않고, 실패한 필드와 위치를 에러로 돌려준다.
     pub fn new(message_bytes: &[u8]) -> Result<Self, ProtocolError> {
-        // 패킷 유형
-        let type_num = message_bytes[0] as usize;
+        Self::new_with(message_bytes, &WireFormat::FULL)
+    }
 
-        // 해당 패킷 유형의 내용물
-        let message_body_bytes = &message_bytes[1..];
+    // 연결마다 협상된 형식으로 몸통을 읽는다
+    pub fn new_with(message_bytes: &[u8], format: &WireFormat) -> Result<Self, ProtocolError> {
+        let mut reader = MessageReader::new(message_bytes);
 
-        match type_num {
+        // 패킷 유형
+        let type_num = reader.u8("type")? as usize;
+
+        let msg = match type_num {
             101 => {
-                let client_id = util::bytes_to_u16_be(&message_body_bytes[..2])? as usize;
-                let worm_body = WormBody::new(client_id, &message_body_bytes[2..])?;
-                Ok(MessageFromServer::ResJoin { client_id, worm_body })
+                reader.set_message("ResJoin");
+                let worm_body = reader.worm_body(format)?;
+                MessageFromServer::ResJoin { client_id: worm_body.client_id(), worm_body }
             },
             102 => {
-                let client_id = util::bytes_to_u16_be(&message_body_bytes[..2])? as usize;
-                Ok(MessageFromServer::ResLeave { client_id })
+                reader.set_message("ResLeave");
+                let client_id = reader.u16("client_id")? as usize;
+                MessageFromServer::ResLeave { client_id }
+            },
+            103 => {
+                reader.set_message("ResWelcome");
+                let version = reader.u16("version")?;
+                let capabilities = Capabilities::from_bits(reader.u32("capabilities")?);
+                let tick_rate = reader.f32("tick_rate")?;
+                MessageFromServer::ResWelcome { version, capabilities, tick_rate }
+            },
+            104 => {
+                reader.set_message("ResReject");
+                let version = reader.u16("version")?;
+                let reason = reader.string("reason")?;
+                MessageFromServer::ResReject { version, reason }
+            },
+            105 => {
+                reader.set_message("ResSession");
+                let client_id = reader.u16("client_id")? as usize;
+                let session_token = reader.u64("session_token")?;
+                MessageFromServer::ResSession { client_id, session_token }
+            },
+            106 => {
+                reader.set_message("ResPing");
+                let timestamp = reader.u64("timestamp")?;
+                MessageFromServer::ResPing { timestamp }
+            },
+            107 => {
+                reader.set_message("ResPong");
+                let timestamp = reader.u64("timestamp")?;
+                MessageFromServer::ResPong { timestamp }
+            },
+            108 => {
+                reader.set_message("ResShutdown");
+                let will_restart = reader.bool("will_restart")?;
+                let restart_in_secs = reader.u32("restart_in_secs")?;
+                let reason = reader.string("reason")?;
+                MessageFromServer::ResShutdown { restart_in_secs: will_restart.then_some(restart_in_secs), reason }
+            },
+            111 => {
+                reader.set_message("ResRoomList");
+                let room_count = reader.u16("rooms.count")? as usize;
+                let mut rooms = Vec::with_capacity(room_count.min(reader.remaining() / 9));
+                for _ in 0..room_count {
+                    rooms.push(reader.room("rooms")?);
+                }
+                MessageFromServer::ResRoomList { rooms }
+            },
+            112 => {
+                reader.set_message("ResRoomEntered");
+                let map_radius = reader.f32("map_radius")?;
+                let name = reader.string("name")?;
+                MessageFromServer::ResRoomEntered { map_radius, name }
+            },
+            113 => {
+                reader.set_message("ResRoomError");
+                let reason = reader.string("reason")?;
+                MessageFromServer::ResRoomError { reason }
             },
             201 => {
-                let client_id = util::bytes_to_u16_be(&message_body_bytes[..2])? as usize;
-                let worm_body = WormBody::new(client_id, &message_body_bytes[2..])?;
-                Ok(MessageFromServer::ResMove { client_id, worm_body })
+                reader.set_message("ResMove");
+                let worm_body = reader.worm_body(format)?;
+                MessageFromServer::ResMove { client_id: worm_body.client_id(), worm_body }
             },
             202 => {
-                let client_id = util::bytes_to_u16_be(&message_body_bytes[..2])? as usize;
-                let food_amount = util::bytes_to_u16_be(&message_body_bytes[2..4])? as usize;
-                let is_ok = if message_body_bytes[4] == 1 { true } else { false };
-                Ok(MessageFromServer::ResEat { client_id, food_amount, is_ok })
+                reader.set_message("ResEat");
+                let client_id = reader.u16("client_id")? as usize;
+                let food_id = reader.u32("food_id")?;
+                let food_amount = reader.u16("food_amount")? as usize;
+                let is_ok = reader.bool("is_ok")?;
+                MessageFromServer::ResEat { client_id, food_id, food_amount, is_ok }
             },
             203 => {
-                let client_id = util::bytes_to_u16_be(&message_body_bytes[..2])? as usize;
-                Ok(MessageFromServer::ResDie { client_id })
+                reader.set_message("ResDie");
+                let client_id = reader.u16("client_id")? as usize;
+                MessageFromServer::ResDie { client_id }
             },
-            n => Err(ProtocolError::from(error::RuleError::InvalidPacketType(n))),
-        }
+            205 => {
+                reader.set_message("ResState");
+                let client_id = reader.u16("client_id")? as usize;
+                let input_seq = reader.u32("input_seq")?;
+                let head = reader.pair("head")?;
+                let dir = reader.pair("dir")?;
+                let boost_remaining = reader.f32("boost_remaining")?;
+                let boost_available = reader.bool("boost_available")?;
+                let max_points = reader.u32("max_points")? as usize;
+                MessageFromServer::ResState { client_id, input_seq, head, dir, boost_remaining, boost_available, max_points }
+            },
+            206 => {
+                reader.set_message("ResMoveDelta");
+                let client_id = reader.u16("client_id")? as usize;
+                let length = reader.u32("length")? as usize;
+                let new_positions = reader.positions("new_positions", format)?;
+                MessageFromServer::ResMoveDelta { client_id, length, new_positions }
+            },
+            207 => {
+                reader.set_message("ResSnapshot");
+                let map_radius = reader.f32("map_radius")?;
+
+                let zone_count = reader.u16("damage_zones.count")? as usize;
+                let mut damage_zones = Vec::with_capacity(zone_count.min(reader.remaining() / 16));
+                for _ in 0..zone_count {
+                    let center = reader.pair("damage_zones.center")?;
+                    let radius = reader.f32("damage_zones.radius")?;
+                    let damage_per_sec = reader.f32("damage_zones.damage_per_sec")?;
+                    damage_zones.push(ZoneInfo { center, radius, damage_per_sec });
+                }
+
+                let (worm_bodies, foods) = read_bodies_and_foods(&mut reader, format)?;
+                MessageFromServer::ResSnapshot { map_radius, damage_zones, worm_bodies, foods }
+            },
+            208 => {
+                reader.set_message("ResFoodSpawn");
+                let mut foods = Vec::with_capacity(reader.remaining() / FoodInfo::SIZE);
+                while reader.remaining() > 0 {
+                    foods.push(reader.food("foods")?);
+                }
+                MessageFromServer::ResFoodSpawn { foods }
+            },
+            209 => {
+                reader.set_message("ResFoodRemove");
+                let mut food_ids = Vec::with_capacity(reader.remaining() / 4);
+                while reader.remaining() > 0 {
+                    food_ids.push(reader.u32("food_ids")?);
+                }
+                MessageFromServer::ResFoodRemove { food_ids }
+            },
+            210 => {
+                reader.set_message("ResKill");
+                let killer_id = reader.u16("killer_id")? as usize;
+                let victim_id = reader.u16("victim_id")? as usize;
+                MessageFromServer::ResKill { killer_id, victim_id }
+            },
+            211 => {
+                reader.set_message("ResEnterView");
+                let (worm_bodies, foods) = read_bodies_and_foods(&mut reader, format)?;
+                MessageFromServer::ResEnterView { worm_bodies, foods }
+            },
+            212 => {
+                reader.set_message("ResLeaveView");
+                let worm_count = reader.u16("client_ids.count")? as usize;
+                let mut client_ids = Vec::with_capacity(worm_count.min(reader.remaining() / 2));
+                for _ in 0..worm_count {
+                    client_ids.push(reader.u16("client_ids")? as usize);
+                }
+                let mut food_ids = Vec::with_capacity(reader.remaining() / 4);
+                while reader.remaining() > 0 {
+                    food_ids.push(reader.u32("food_ids")?);
+                }
+                MessageFromServer::ResLeaveView { client_ids, food_ids }
+            },
+            n => return Err(ProtocolError::from(error::RuleError::InvalidPacketType(n))),
+        };
+        reader.finish()?;
+
+        Ok(msg)
     }
 
-    pub fn make_bytes(&self) -> Vec<u8> {
+    // 길이(u16) 필드까지 붙인 프레임. 테스트처럼 코덱 없이 바로 소켓에 쓸 때 쓴다.
+    // 길이 필드에 담을 수 없는 메세지는 잘라내지 않고 에러를 낸다.
+    pub fn make_bytes(&self) -> Result<Vec<u8>, NetworkError> {
+        let message_bytes = self.make_message_bytes_with(&WireFormat::FULL);
+        let length = u16::try_from(message_bytes.len())
+            .map_err(|_| NetworkError::TooLongMsg { length: message_bytes.len(), max_length: u16::MAX as usize })?;
+
+        let mut packet = Vec::with_capacity(2 + message_bytes.len());
+        packet.extend(u16_be_to_bytes(length));
+        packet.extend(message_bytes);
+        Ok(packet)
+    }
+
+    // 길이 필드를 뺀 유형 + 메세지. 길이 필드는 연결마다 협상된 형식으로 코덱이 붙인다.
+    pub fn make_message_bytes_with(&self, format: &WireFormat) -> Vec<u8> {
         match *self {
-            MessageFromServer::ResJoin { client_id, ref worm_body } => {
-                let worm_body_bytes = worm_body.make_bytes();
-                let mut packet = Vec::with_capacity(5 + worm_body_bytes.len());
-                packet.extend(u16_be_to_bytes(1 + worm_body_bytes.len() as u16));
+            MessageFromServer::ResJoin { client_id: _, ref worm_body } => {
+                let worm_body_bytes = worm_body.make_bytes_with(format);
+                let mut packet = Vec::with_capacity(3 + worm_body_bytes.len());
                 packet.push(101u8);
                 packet.extend(worm_body_bytes);
                 packet
             },
             MessageFromServer::ResLeave { client_id } => {
-                let mut packet = Vec::with_capacity(5);
-                packet.extend(u16_be_to_bytes(3));
+                let mut packet = Vec::with_capacity(3);
                 packet.push(102u8);
                 packet.extend(u16_be_to_bytes(client_id as u16));
                 packet
             },
-            MessageFromServer::ResMove { client_id, ref worm_body } => {
-                let worm_body_bytes = worm_body.make_bytes();
+            MessageFromServer::ResWelcome { version, capabilities, tick_rate } => {
+                let mut packet = Vec::with_capacity(11);
+                packet.push(103u8);
+                packet.extend(u16_be_to_bytes(version));
+                packet.extend(util::u32_be_to_bytes(capabilities.bits()));
+                packet.extend(tick_rate.to_be_bytes());
+                packet
+            },
+            MessageFromServer::ResReject { version, ref reason } => {
+                let mut packet = Vec::with_capacity(3 + reason.len());
+                packet.push(104u8);
+                packet.extend(u16_be_to_bytes(version));
+                packet.extend(reason.as_bytes());
+                packet
+            },
+            MessageFromServer::ResSession { client_id, session_token } => {
+                let mut packet = Vec::with_capacity(11);
+                packet.push(105u8);
+                packet.extend(u16_be_to_bytes(client_id as u16));
+                packet.extend(session_token.to_be_bytes());
+                packet
+            },
+            MessageFromServer::ResPing { timestamp } => {
+                let mut packet = Vec::with_capacity(9);
+                packet.push(106u8);
+                packet.extend(timestamp.to_be_bytes());
+                packet
+            },
+            MessageFromServer::ResPong { timestamp } => {
+                let mut packet = Vec::with_capacity(9);
+                packet.push(107u8);
+                packet.extend(timestamp.to_be_bytes());
+                packet
+            },
+            MessageFromServer::ResShutdown { restart_in_secs, ref reason } => {
+                let mut packet = Vec::with_capacity(6 + reason.len());
+                packet.push(108u8);
+                packet.push(if restart_in_secs.is_some() { 1 } else { 0 });
+                packet.extend(util::u32_be_to_bytes(restart_in_secs.unwrap_or(0)));
+                packet.extend(reason.as_bytes());
+                packet
+            },
+            MessageFromServer::ResRoomList { ref rooms } => {
+                let rooms_bytes = rooms.iter().map(|room| room.make_bytes()).collect::<Vec<_>>();
+                let mut packet = Vec::with_capacity(3 + rooms_bytes.iter().map(|bytes| bytes.len()).sum::<usize>());
+                packet.push(111u8);
+                packet.extend(u16_be_to_bytes(rooms_bytes.len() as u16));
+                for room_bytes in rooms_bytes {
+                    packet.extend(room_bytes);
+                }
+                packet
+            },
+            MessageFromServer::ResRoomEntered { map_radius, ref name } => {
+                let mut packet = Vec::with_capacity(5 + name.len());
+                packet.push(112u8);
+                packet.extend(map_radius.to_be_bytes());
+                packet.extend(name.as_bytes());
+                packet
+            },
+            MessageFromServer::ResRoomError { ref reason } => {
+                let mut packet = Vec::with_capacity(1 + reason.len());
+                packet.push(113u8);
+                packet.extend(reason.as_bytes());
+                packet
+            },
+            MessageFromServer::ResMove { client_id: _, ref worm_body } => {
+                let worm_body_bytes = worm_body.make_bytes_with(format);
 
                 // message type length (1 bytes) + client id (2 bytes) + worm positions (N bytes)
-                let mut packet = Vec::with_capacity(5 + worm_body_bytes.len());
-                packet.extend(u16_be_to_bytes(1 + worm_body_bytes.len() as u16));
+                let mut packet = Vec::with_capacity(3 + worm_body_bytes.len());
                 packet.push(201u8);
                 packet.extend(worm_body_bytes);
                 packet
             },
-            MessageFromServer::ResEat { client_id, food_amount, is_ok } => {
-                let mut packet = Vec::with_capacity(8);
-                packet.extend(u16_be_to_bytes(6));
+            MessageFromServer::ResEat { client_id, food_id, food_amount, is_ok } => {
+                let mut packet = Vec::with_capacity(10);
                 packet.push(202u8);
                 packet.extend(u16_be_to_bytes(client_id as u16));
+                packet.extend(util::u32_be_to_bytes(food_id));
                 packet.extend(u16_be_to_bytes(food_amount as u16));
                 packet.push(if is_ok { 1 } else { 0 });
                 packet
             },
             MessageFromServer::ResDie { client_id } => {
-                let mut packet = Vec::with_capacity(5);
-                packet.extend(u16_be_to_bytes(3));
+                let mut packet = Vec::with_capacity(3);
                 packet.push(203u8);
                 packet.extend(u16_be_to_bytes(client_id as u16));
                 packet
             },
+            MessageFromServer::ResState { client_id, input_seq, head, dir, boost_remaining, boost_available, max_points } => {
+                let mut packet = Vec::with_capacity(32);
+                packet.push(205u8);
+                packet.extend(u16_be_to_bytes(client_id as u16));
+                packet.extend(util::u32_be_to_bytes(input_seq));
+                packet.extend(util::positions_to_bytes(&[head, dir]));
+                packet.extend(boost_remaining.to_be_bytes());
+                packet.push(if boost_available { 1 } else { 0 });
+                packet.extend(util::u32_be_to_bytes(max_points as u32));
+                packet
+            },
+            MessageFromServer::ResMoveDelta { client_id, length, ref new_positions } => {
+                let positions_bytes = format.positions_to_bytes(new_positions);
+                let mut packet = Vec::with_capacity(7 + positions_bytes.len());
+                packet.push(206u8);
+                packet.extend(u16_be_to_bytes(client_id as u16));
+                packet.extend(util::u32_be_to_bytes(length as u32));
+                packet.extend(positions_bytes);
+                packet
+            },
+            MessageFromServer::ResSnapshot { map_radius, ref damage_zones, ref worm_bodies, ref foods } => {
+                let bodies_and_foods = bodies_and_foods_to_bytes(worm_bodies, foods, format);
+                let mut packet = Vec::with_capacity(7 + damage_zones.len() * 16 + bodies_and_foods.len());
+                packet.push(207u8);
+                packet.extend(map_radius.to_be_bytes());
+
+                packet.extend(u16_be_to_bytes(damage_zones.len() as u16));
+                for zone in damage_zones {
+                    packet.extend(util::positions_to_bytes(&[zone.center]));
+                    packet.extend(zone.radius.to_be_bytes());
+                    packet.extend(zone.damage_per_sec.to_be_bytes());
+                }
+
+                packet.extend(bodies_and_foods);
+                packet
+            },
+            MessageFromServer::ResFoodSpawn { ref foods } => {
+                let mut packet = Vec::with_capacity(1 + foods.len() * FoodInfo::SIZE);
+                packet.push(208u8);
+                for food in foods {
+                    packet.extend(food.make_bytes());
+                }
+                packet
+            },
+            MessageFromServer::ResFoodRemove { ref food_ids } => {
+                let mut packet = Vec::with_capacity(1 + food_ids.len() * 4);
+                packet.push(209u8);
+                for food_id in food_ids {
+                    packet.extend(util::u32_be_to_bytes(*food_id));
+                }
+                packet
+            },
+            MessageFromServer::ResKill { killer_id, victim_id } => {
+                let mut packet = Vec::with_capacity(5);
+                packet.push(210u8);
+                packet.extend(u16_be_to_bytes(killer_id as u16));
+                packet.extend(u16_be_to_bytes(victim_id as u16));
+                packet
+            },
+            MessageFromServer::ResEnterView { ref worm_bodies, ref foods } => {
+                let bodies_and_foods = bodies_and_foods_to_bytes(worm_bodies, foods, format);
+                let mut packet = Vec::with_capacity(1 + bodies_and_foods.len());
+                packet.push(211u8);
+                packet.extend(bodies_and_foods);
+                packet
+            },
+            MessageFromServer::ResLeaveView { ref client_ids, ref food_ids } => {
+                let mut packet = Vec::with_capacity(3 + client_ids.len() * 2 + food_ids.len() * 4);
+                packet.push(212u8);
+                packet.extend(u16_be_to_bytes(client_ids.len() as u16));
+                for client_id in client_ids {
+                    packet.extend(u16_be_to_bytes(*client_id as u16));
+                }
+                for food_id in food_ids {
+                    packet.extend(util::u32_be_to_bytes(*food_id));
+                }
+                packet
+            },
         }
     }
 
+    // 스냅샷이 u16 길이 필드에도 들어가도록 여러 프레임으로 나눈다.
+    // 첫 프레임은 ResSnapshot이고, 넘치는 지렁이와 먹이는 바로 뒤에 ResEnterView로 이어서 보낸다.
+    // 크기는 형식마다 다르므로 가장 길게 쓰이는 경우로 어림한다. 혼자서도 넘치는 몸통은 나눌 수 없어서 그대로 한 프레임에 담는다.
+    pub fn snapshot_frames(map_radius: f32, damage_zones: Vec<ZoneInfo>, worm_bodies: Vec<WormBody>, foods: Vec<FoodInfo>) -> Vec<Self> {
+        let mut chunks = vec![(Vec::new(), Vec::new())];
+        // 유형, 맵 반지름, 영역 수, 영역들, 지렁이 수, 먹이 수
+        let mut length = 1 + 4 + 2 + damage_zones.len() * 16 + 2 + 4;
+        for worm_body in worm_bodies {
+            let size = 4 + worm_body.max_bytes_len();
+            fit_in_frame(&mut chunks, &mut length, size).0.push(worm_body);
+        }
+        for food in foods {
+            fit_in_frame(&mut chunks, &mut length, FoodInfo::SIZE).1.push(food);
+        }
+
+        let mut chunks = chunks.into_iter();
+        let (worm_bodies, foods) = chunks.next().unwrap_or_default();
+        let mut frames = vec![MessageFromServer::ResSnapshot { map_radius, damage_zones, worm_bodies, foods }];
+        frames.extend(chunks.map(|(worm_bodies, foods)| MessageFromServer::ResEnterView { worm_bodies, foods }));
+        frames
+    }
+}
+
+// u16 길이 필드에 담을 수 있는 가장 긴 프레임 (유형 + 메세지)
+const SNAPSHOT_FRAME_LIMIT: usize = u16::MAX as usize;
+
+// 지금 채우는 프레임에 size만큼 더 들어가지 않으면 ResEnterView로 보낼 새 프레임을 시작한다.
+fn fit_in_frame<'a>(chunks: &'a mut Vec<(Vec<WormBody>, Vec<FoodInfo>)>, length: &mut usize, size: usize) -> &'a mut (Vec<WormBody>, Vec<FoodInfo>) {
+    let is_empty = chunks.last().is_none_or(|(worm_bodies, foods)| worm_bodies.is_empty() && foods.is_empty());
+    if *length + size > SNAPSHOT_FRAME_LIMIT && !is_empty {
+        chunks.push((Vec::new(), Vec::new()));
+        // 유형, 지렁이 수, 먹이 수
+        *length = 1 + 2 + 4;
+    }
+    *length += size;
+    chunks.last_mut().unwrap()
+}
+
+// ResSnapshot과 ResEnterView가 같이 쓰는 부분.
+// 지렁이 수(u16), (몸통 길이(u32), 지렁이 몸통 정보) 반복, 먹이 수(u32), 먹이 반복
+fn bodies_and_foods_to_bytes(worm_bodies: &[WormBody], foods: &[FoodInfo], format: &WireFormat) -> Vec<u8> {
+    let worm_bodies_bytes = worm_bodies.iter().map(|worm_body| worm_body.make_bytes_with(format)).collect::<Vec<_>>();
+    let worm_bodies_length = worm_bodies_bytes.iter().map(|bytes| 4 + bytes.len()).sum::<usize>();
+    let mut bytes = Vec::with_capacity(6 + worm_bodies_length + foods.len() * FoodInfo::SIZE);
+
+    bytes.extend(u16_be_to_bytes(worm_bodies_bytes.len() as u16));
+    for body_bytes in worm_bodies_bytes {
+        bytes.extend(util::u32_be_to_bytes(body_bytes.len() as u32));
+        bytes.extend(body_bytes);
+    }
+
+    bytes.extend(util::u32_be_to_bytes(foods.len() as u32));
+    for food in foods {
+        bytes.extend(food.make_bytes());
+    }
+    bytes
+}
+
+fn read_bodies_and_foods(reader: &mut MessageReader, format: &WireFormat) -> Result<(Vec<WormBody>, Vec<FoodInfo>), ProtocolError> {
+    let worm_count = reader.u16("worm_bodies.count")? as usize;
+    let mut worm_bodies = Vec::with_capacity(worm_count.min(reader.remaining() / 4));
+    for _ in 0..worm_count {
+        worm_bodies.push(reader.sized_worm_body(format)?);
+    }
+
+    // 개수는 믿지 않고, 남은 바이트로 담을 수 있는 만큼만 미리 잡는다.
+    let food_count = reader.u32("foods.count")? as usize;
+    let mut foods = Vec::with_capacity(food_count.min(reader.remaining() / FoodInfo::SIZE));
+    for _ in 0..food_count {
+        foods.push(reader.food("foods")?);
+    }
+    Ok((worm_bodies, foods))
 }
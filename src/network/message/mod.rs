@@ -1,25 +1,9 @@
-use crate::network::error::NetworkError;
-use crate::network::util::bytes_to_u16_be;
-
 pub mod message_from_client;
 pub mod message_from_server;
 pub mod worm_body;
-
-// 수신된 패킷의 길이 체크.
-// 충분하면, 사용하게 될 바이트를 제외한 잔여 패킷의 사이즈를 리턴한다.
-//  길이    |   유형  |   메세지
-// 2 bytes | 1 byte | N bytes ..
-pub fn validate_packet_length(bytes: &[u8]) -> Result<usize, NetworkError> {
-    let expected_length = bytes_to_u16_be(bytes)? as usize;
-    let actual_length = bytes.len().saturating_sub(2);
-
-    // 메세지의 길이 필드에 들어있는 값만큼의 실제 바이트 배열이 들어오지 않았을 경우
-    if actual_length < expected_length {
-        return Err(NetworkError::ShortMsg { expected_length, actual_length, });
-    }
-
-    Ok(actual_length - expected_length)
-}
+pub mod snapshot;
+pub mod room;
+mod reader;
 
 #[cfg(test)]
 mod tests {
@@ -28,7 +12,12 @@ mod tests {
     use std::net::{Shutdown, TcpStream};
     use std::thread::sleep;
     use std::time::Duration;
+    use bytes::BytesMut;
+    use tokio_util::codec::{Decoder, Encoder};
     use tracing::{error, info};
+    use crate::game::world::World;
+    use crate::network::codec::{ClientCodec, FrameLength};
+    use crate::network::protocol::{Capabilities, WireFormat, PROTOCOL_VERSION};
     use crate::network::message::message_from_client::MessageFromClient;
     use crate::network::message::message_from_server::MessageFromServer;
     use crate::network::message::worm_body::WormBody;
@@ -49,15 +38,34 @@ mod tests {
     // fixture
     struct TestContext {
         stream: TcpStream,
+        codec: ClientCodec,
+        buffer: BytesMut,
     }
 
     impl TestContext {
+        // 서버는 ReqHello부터 받으므로 인사까지 마친 상태로 만든다.
         fn new(ip_port: &'static str) -> io::Result<Self> {
+            let mut fixture = Self::connect(ip_port)?;
+            let hello = MessageFromClient::ReqHello { version: PROTOCOL_VERSION, capabilities: Capabilities::SUPPORTED };
+            let packet = fixture.frame(&hello);
+            fixture.stream.write_all(&packet)?;
+            let welcome = fixture.read_response();
+            info!("server message: {:?}", welcome);
+            let MessageFromServer::ResWelcome { version: PROTOCOL_VERSION, capabilities, .. } = welcome else {
+                panic!("unexpected message: {:?}", welcome);
+            };
+            // 이후 몸통은 협상된 형식으로 온다
+            fixture.codec.set_format(WireFormat::negotiated(capabilities, World::MAP_RADIUS));
+            fixture.codec.set_frame_length(FrameLength::negotiated(capabilities));
+            Ok(fixture)
+        }
+
+        fn connect(ip_port: &'static str) -> io::Result<Self> {
             // before each
             match TcpStream::connect(ip_port) {
                 Ok(stream) => {
                     info!("connected to server..");
-                    Ok(Self { stream })
+                    Ok(Self { stream, codec: ClientCodec::new(), buffer: BytesMut::new() })
                 },
                 Err(_) => {
                     error!("failed to connect to server..");
@@ -66,12 +74,43 @@ mod tests {
             }
         }
 
+        // 한 번의 read에 메세지가 여러 개 붙어오거나 잘려올 수 있으므로 코덱으로 메세지 하나를 잘라낸다.
         fn read_response(&mut self) -> MessageFromServer {
             let mut read_packet = [0u8; 1024];
-            let read_count = self.stream.read(&mut read_packet).unwrap();
-            let read_packet: Vec<u8> = read_packet[2..read_count].to_owned();
-            // info!("read packet: {:?}", read_packet);
-            MessageFromServer::new(&read_packet).unwrap()
+            loop {
+                if let Some(msg) = self.codec.decode(&mut self.buffer).unwrap() {
+                    return msg;
+                }
+                let read_count = self.stream.read(&mut read_packet).unwrap();
+                assert!(read_count > 0, "server closed the connection.");
+                self.buffer.extend_from_slice(&read_packet[..read_count]);
+            }
+        }
+
+        // 협상한 길이 필드로 프레임을 만든다. 파편화 테스트에서 잘라 보낼 수 있도록 바이트로 돌려준다.
+        fn frame(&mut self, msg: &MessageFromClient) -> Vec<u8> {
+            let mut buffer = BytesMut::new();
+            self.codec.encode(msg, &mut buffer).unwrap();
+            buffer.to_vec()
+        }
+
+        // 서버가 주기적으로 보내는 ResPing은 건너뛰고 그 다음 메세지를 읽는다.
+        fn read_reply(&mut self) -> MessageFromServer {
+            loop {
+                match self.read_response() {
+                    MessageFromServer::ResPing { .. } => continue,
+                    msg => return msg,
+                }
+            }
+        }
+
+        // ReqJoin 뒤에 본인에게만 오는 ResSession에서 서버가 정해준 id를 꺼낸다.
+        fn read_session(&mut self) -> usize {
+            loop {
+                if let MessageFromServer::ResSession { client_id, .. } = self.read_response() {
+                    return client_id;
+                }
+            }
         }
     }
 
@@ -79,32 +118,60 @@ mod tests {
         fn drop(&mut self) {
             // after each
             info!("close ..");
-            match self.stream.shutdown(Shutdown::Write) {
-                Ok(_) => {},
-                Err(_) => {}
-            };
+            let _ = self.stream.shutdown(Shutdown::Write);
         }
     }
 
+    // 버전이 다른 클라이언트는 사유와 함께 거절되어야 한다
+    #[test]
+    fn test_incompatible_version() -> Result<(), Box<dyn std::error::Error>> {
+        init_tracing();
+        let mut fixture = TestContext::connect("127.0.0.1:8888")?;
+
+        let packet = MessageFromClient::ReqHello { version: PROTOCOL_VERSION + 1, capabilities: Capabilities::empty() }.make_bytes()?;
+        fixture.stream.write_all(&packet)?;
+
+        let server_message = fixture.read_response();
+        info!("server message: {:?}", server_message);
+        assert!(matches!(server_message, MessageFromServer::ResReject { version: PROTOCOL_VERSION, .. }));
+
+        Ok(())
+    }
+
+    // 메세지 형식이 바뀌기 전의 클라이언트도 거절되어야 한다
+    #[test]
+    fn test_old_version() -> Result<(), Box<dyn std::error::Error>> {
+        init_tracing();
+        let mut fixture = TestContext::connect("127.0.0.1:8888")?;
+
+        let packet = MessageFromClient::ReqHello { version: PROTOCOL_VERSION - 1, capabilities: Capabilities::SUPPORTED }.make_bytes()?;
+        fixture.stream.write_all(&packet)?;
+
+        let server_message = fixture.read_response();
+        info!("server message: {:?}", server_message);
+        assert!(matches!(server_message, MessageFromServer::ResReject { version: PROTOCOL_VERSION, .. }));
+
+        Ok(())
+    }
+
     // 핏이 딱 맞는 메세지 테스트
     #[test]
     fn test_good_size_packet() -> Result<(), Box<dyn std::error::Error>> {
         init_tracing();
         let mut fixture = TestContext::new("127.0.0.1:8888")?;
-        let client_id = 1234;
 
-        let packet = MessageFromClient::ReqJoin { client_id }.make_bytes();
-        let _ = fixture.stream.write_all(&packet)?;
+        let packet = fixture.frame(&MessageFromClient::ReqJoin { session_token: 0 });
+        fixture.stream.write_all(&packet)?;
         info!("join to the game");
 
         info!("sleep 0.1s ..");
         sleep(Duration::from_millis(100));
 
-        let server_message = fixture.read_response();
-        info!("server message: {:?}", server_message);
+        let client_id = fixture.read_session();
+        info!("assigned client id: {}", client_id);
 
-        let packet = MessageFromClient::ReqLeave { client_id }.make_bytes();
-        let _ = fixture.stream.write_all(&packet)?;
+        let packet = fixture.frame(&MessageFromClient::ReqLeave { client_id });
+        fixture.stream.write_all(&packet)?;
         info!("leave the game");
 
         info!("sleep 0.1s ..");
@@ -124,9 +191,8 @@ mod tests {
     fn test_divided_2_packets() -> Result<(), Box<dyn std::error::Error>> {
         init_tracing();
         let mut fixture = TestContext::new("127.0.0.1:8888")?;
-        let client_id = 1234;
 
-        let packet = MessageFromClient::ReqJoin { client_id }.make_bytes();
+        let packet = fixture.frame(&MessageFromClient::ReqJoin { session_token: 0 });
         let _ = fixture.stream.write_all(&packet[..2]);
 
         info!("sleep 0.1s ..");
@@ -151,9 +217,8 @@ mod tests {
     fn test_divided_3_packets() -> Result<(), Box<dyn std::error::Error>> {
         init_tracing();
         let mut fixture = TestContext::new("127.0.0.1:8888")?;
-        let client_id = 1234;
 
-        let packet = MessageFromClient::ReqJoin { client_id }.make_bytes();
+        let packet = fixture.frame(&MessageFromClient::ReqJoin { session_token: 0 });
         let _ = fixture.stream.write_all(&packet[..2]);
 
         info!("sleep 0.1s ..");
@@ -182,16 +247,25 @@ mod tests {
     fn test_req_move() -> Result<(), Box<dyn std::error::Error>> {
         init_tracing();
         let mut fixture = TestContext::new("127.0.0.1:8888")?;
-        let client_id = 1234;
+
+        // 서버 월드에 지렁이가 있어야 몸통 보고를 받아주므로 먼저 참가한다.
+        let packet = fixture.frame(&MessageFromClient::ReqJoin { session_token: 0 });
+        let _ = fixture.stream.write_all(&packet);
+
+        info!("sleep 0.1s ..");
+        sleep(Duration::from_millis(100));
+
+        // 몸통 보고는 서버가 정해준 id로 해야 받아준다.
+        let client_id = fixture.read_session();
         let worm_body = WormBody::new(
             client_id,
             &[
                 util::color_to_bytes(&(0.5019608_f32, 0.5019608_f32, 0.5019608, 1.0_f32)),
-                util::positions_to_bytes(&vec![(1_f32, 1_f32), (2_f32, 2_f32), (3_f32, 3_f32)]),
+                util::positions_to_bytes(&[(1_f32, 1_f32), (2_f32, 2_f32), (3_f32, 3_f32)]),
             ].concat(),
         )?;
 
-        let packet = MessageFromClient::ReqMove { client_id, worm_body }.make_bytes();
+        let packet = fixture.frame(&MessageFromClient::ReqMove { client_id, worm_body });
         let _ = fixture.stream.write_all(&packet);
 
         info!("sleep 0.1s ..");
@@ -205,4 +279,67 @@ mod tests {
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    // ResState에는 몸통이 없으므로 주인은 그 전에 자기 몸통을 ResMove(ResMoveDelta)로 받아야 한다
+    #[test]
+    fn test_state_follows_own_body() -> Result<(), Box<dyn std::error::Error>> {
+        init_tracing();
+        let mut fixture = TestContext::new("127.0.0.1:8888")?;
+
+        let packet = fixture.frame(&MessageFromClient::ReqJoin { session_token: 0 });
+        fixture.stream.write_all(&packet)?;
+        let client_id = fixture.read_session();
+
+        let mut body_received = false;
+        loop {
+            match fixture.read_reply() {
+                MessageFromServer::ResMove { client_id: id, .. } | MessageFromServer::ResMoveDelta { client_id: id, .. } if id == client_id => {
+                    body_received = true;
+                },
+                MessageFromServer::ResState { client_id: id, .. } if id == client_id => break,
+                _ => {},
+            }
+        }
+        assert!(body_received);
+
+        Ok(())
+    }
+
+    // 방을 만들면 그 방의 맵 크기로 참가하고, 목록에도 보여야 한다
+    #[test]
+    fn test_create_room() -> Result<(), Box<dyn std::error::Error>> {
+        init_tracing();
+        let mut fixture = TestContext::new("127.0.0.1:8888")?;
+
+        // 테스트끼리 방 이름이 겹치지 않도록 포트를 붙인다
+        let name = format!("test-{}", fixture.stream.local_addr()?.port());
+        let packet = fixture.frame(&MessageFromClient::ReqCreateRoom { map_radius: 800.0, max_players: 4, name: name.clone() });
+        fixture.stream.write_all(&packet)?;
+        let server_message = fixture.read_reply();
+        info!("server message: {:?}", server_message);
+        assert!(matches!(server_message, MessageFromServer::ResRoomEntered { map_radius: 800.0, name: ref entered } if *entered == name));
+
+        // 같은 이름으로는 다시 만들 수 없다
+        let packet = fixture.frame(&MessageFromClient::ReqCreateRoom { map_radius: 800.0, max_players: 4, name: name.clone() });
+        fixture.stream.write_all(&packet)?;
+        assert!(matches!(fixture.read_reply(), MessageFromServer::ResRoomError { .. }));
+
+        let packet = fixture.frame(&MessageFromClient::ReqRoomList);
+        fixture.stream.write_all(&packet)?;
+        let MessageFromServer::ResRoomList { rooms } = fixture.read_reply() else {
+            panic!("expected room list.");
+        };
+        assert!(rooms.iter().any(|room| room.name == name && room.players == 1 && room.max_players == 4));
+
+        let packet = fixture.frame(&MessageFromClient::ReqJoin { session_token: 0 });
+        fixture.stream.write_all(&packet)?;
+        loop {
+            if let MessageFromServer::ResSnapshot { map_radius, .. } = fixture.read_reply() {
+                assert_eq!(map_radius, 800.0);
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
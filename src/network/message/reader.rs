@@ -0,0 +1,423 @@
+use crate::network::error::{NetworkError, ProtocolError};
+use crate::network::message::room::RoomInfo;
+use crate::network::message::snapshot::FoodInfo;
+use crate::network::message::worm_body::WormBody;
+use crate::network::protocol::WireFormat;
+use crate::network::util;
+
+// 메세지 바이트를 앞에서부터 필드 단위로 읽는 커서.
+// 모든 읽기가 길이를 먼저 확인하므로 어떤 입력이 들어와도 패닉하지 않고,
+// 실패하면 메세지 이름, 필드 이름, 바이트 위치를 담은 ProtocolError::Decode를 돌려준다.
+// offset은 유형 필드를 포함한 메세지 바이트 기준 (길이 필드 제외).
+pub(crate) struct MessageReader<'a> {
+    message: &'static str,
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> MessageReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { message: "message", bytes, offset: 0 }
+    }
+
+    // 유형을 읽고 나면 에러에 찍힐 메세지 이름을 정해준다.
+    pub(crate) fn set_message(&mut self, message: &'static str) {
+        self.message = message;
+    }
+
+    fn error(&self, field: &'static str, offset: usize, source: NetworkError) -> ProtocolError {
+        ProtocolError::Decode { message: self.message, field, offset, source }
+    }
+
+    fn take<const N: usize>(&mut self, field: &'static str) -> Result<[u8; N], ProtocolError> {
+        let remaining = &self.bytes[self.offset..];
+        let Some(bytes) = remaining.first_chunk::<N>() else {
+            return Err(self.error(field, self.offset, NetworkError::ShortMsg {
+                expected_length: N,
+                actual_length: remaining.len(),
+            }));
+        };
+        self.offset += N;
+        Ok(*bytes)
+    }
+
+    pub(crate) fn u8(&mut self, field: &'static str) -> Result<u8, ProtocolError> {
+        Ok(self.take::<1>(field)?[0])
+    }
+
+    pub(crate) fn u16(&mut self, field: &'static str) -> Result<u16, ProtocolError> {
+        Ok(u16::from_be_bytes(self.take(field)?))
+    }
+
+    pub(crate) fn u32(&mut self, field: &'static str) -> Result<u32, ProtocolError> {
+        Ok(u32::from_be_bytes(self.take(field)?))
+    }
+
+    pub(crate) fn u64(&mut self, field: &'static str) -> Result<u64, ProtocolError> {
+        Ok(u64::from_be_bytes(self.take(field)?))
+    }
+
+    pub(crate) fn bool(&mut self, field: &'static str) -> Result<bool, ProtocolError> {
+        Ok(self.u8(field)? == 1)
+    }
+
+    pub(crate) fn f32(&mut self, field: &'static str) -> Result<f32, ProtocolError> {
+        let offset = self.offset;
+        let value = f32::from_be_bytes(self.take(field)?);
+        if !value.is_finite() {
+            return Err(self.error(field, offset, NetworkError::NotFinite(value)));
+        }
+        Ok(value)
+    }
+
+    pub(crate) fn pair(&mut self, field: &'static str) -> Result<(f32, f32), ProtocolError> {
+        Ok((self.f32(field)?, self.f32(field)?))
+    }
+
+    // 메세지 끝까지를 UTF-8 문자열로 읽는다
+    pub(crate) fn string(&mut self, field: &'static str) -> Result<String, ProtocolError> {
+        let remaining = &self.bytes[self.offset..];
+        let Ok(text) = std::str::from_utf8(remaining) else {
+            return Err(self.error(field, self.offset, NetworkError::InvalidMsg { input_length: remaining.len() }));
+        };
+        self.offset = self.bytes.len();
+        Ok(text.to_string())
+    }
+
+    // 길이(u8)만큼을 UTF-8 문자열로 읽는다
+    pub(crate) fn short_string(&mut self, field: &'static str) -> Result<String, ProtocolError> {
+        let length = self.u8(field)? as usize;
+        let remaining = &self.bytes[self.offset..];
+        let Some(bytes) = remaining.get(..length) else {
+            return Err(self.error(field, self.offset, NetworkError::ShortMsg {
+                expected_length: length,
+                actual_length: remaining.len(),
+            }));
+        };
+        let Ok(text) = std::str::from_utf8(bytes) else {
+            return Err(self.error(field, self.offset, NetworkError::InvalidMsg { input_length: length }));
+        };
+        self.offset += length;
+        Ok(text.to_string())
+    }
+
+    // client id(u16), 색상, 좌표 반복. 좌표는 메세지 끝까지 이어진다.
+    // 색상과 좌표의 형식은 WireFormat 참고
+    pub(crate) fn worm_body(&mut self, format: &WireFormat) -> Result<WormBody, ProtocolError> {
+        let client_id = self.u16("client_id")? as usize;
+        let color = if format.compact_colors {
+            util::compact_bytes_to_color(self.take("worm_body.color")?)
+        } else {
+            (
+                self.f32("worm_body.color")?,
+                self.f32("worm_body.color")?,
+                self.f32("worm_body.color")?,
+                self.f32("worm_body.color")?,
+            )
+        };
+
+        let positions = self.positions("worm_body.positions", format)?;
+
+        Ok(WormBody::from_parts(client_id, color, positions))
+    }
+
+    // 길이(u32)가 앞에 붙은 몸통. 스냅샷처럼 몸통 뒤에 다른 필드가 이어질 때 쓴다.
+    pub(crate) fn sized_worm_body(&mut self, format: &WireFormat) -> Result<WormBody, ProtocolError> {
+        let offset = self.offset;
+        let length = self.u32("worm_body.length")? as usize;
+        let remaining = self.remaining();
+        if length > remaining {
+            return Err(self.error("worm_body.length", offset, NetworkError::ShortMsg { expected_length: length, actual_length: remaining }));
+        }
+
+        // 몸통은 끝까지 읽으므로 잠깐 끝을 몸통 길이로 줄여서 읽는다. 오프셋은 메세지 기준 그대로.
+        let bytes = self.bytes;
+        self.bytes = &bytes[..self.offset + length];
+        let worm_body = self.worm_body(format);
+        self.bytes = bytes;
+        worm_body
+    }
+
+    pub(crate) fn food(&mut self, field: &'static str) -> Result<FoodInfo, ProtocolError> {
+        let id = self.u32(field)?;
+        let position = self.pair(field)?;
+        let growth = self.u8(field)? as usize;
+        Ok(FoodInfo { id, position, growth })
+    }
+
+    // 방 이름(u8 길이 + UTF-8), 인원(u16), 최대 인원(u16), 맵 반지름(f32)
+    pub(crate) fn room(&mut self, field: &'static str) -> Result<RoomInfo, ProtocolError> {
+        let name = self.short_string(field)?;
+        let players = self.u16(field)? as usize;
+        let max_players = self.u16(field)? as usize;
+        let map_radius = self.f32(field)?;
+        Ok(RoomInfo { name, players, max_players, map_radius })
+    }
+
+    // 아직 읽지 않은 바이트 수
+    pub(crate) fn remaining(&self) -> usize {
+        self.bytes.len() - self.offset
+    }
+
+    // 메세지 끝까지 이어지는 좌표들
+    pub(crate) fn positions(&mut self, field: &'static str, format: &WireFormat) -> Result<Vec<(f32, f32)>, ProtocolError> {
+        let (width, range) = if format.fixed_positions {
+            let offset = self.offset;
+            let width = self.u8(field)? as usize;
+            if !(2..=3).contains(&width) {
+                return Err(self.error(field, offset, NetworkError::InvalidMsg { input_length: self.bytes.len() - offset }));
+            }
+            let offset = self.offset;
+            let range = self.f32(field)?;
+            if range <= 0.0 {
+                return Err(self.error(field, offset, NetworkError::InvalidMsg { input_length: self.bytes.len() - offset }));
+            }
+            (width, Some(range))
+        } else {
+            (4, None)
+        };
+
+        let remaining = self.bytes.len() - self.offset;
+        if !remaining.is_multiple_of(width * 2) {
+            return Err(self.error(field, self.offset, NetworkError::InvalidMsg { input_length: remaining }));
+        }
+
+        let positions = match range {
+            Some(range) => util::fixed_bytes_to_positions(&self.bytes[self.offset..], range, width)
+                .map_err(|e| self.error(field, self.offset, e))?,
+            None => {
+                let mut positions = Vec::with_capacity(remaining / 8);
+                while self.offset < self.bytes.len() {
+                    positions.push(self.pair(field)?);
+                }
+                positions
+            },
+        };
+        self.offset = self.bytes.len();
+        Ok(positions)
+    }
+
+    // 고정 길이 메세지 뒤에 붙은 쓰레기 바이트는 잘못 만든 메세지로 본다.
+    pub(crate) fn finish(&self) -> Result<(), ProtocolError> {
+        let remaining_length = self.bytes.len() - self.offset;
+        if remaining_length > 0 {
+            return Err(self.error("end", self.offset, NetworkError::TrailingBytes { remaining_length }));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::network::codec::{ClientCodec, ServerCodec};
+    use crate::network::error::{NetworkError, ProtocolError};
+    use crate::network::message::message_from_client::MessageFromClient;
+    use crate::network::message::message_from_server::MessageFromServer;
+    use crate::network::protocol::{Capabilities, WireFormat};
+    use bytes::BytesMut;
+    use proptest::prelude::*;
+    use tokio_util::codec::Decoder;
+
+    #[test]
+    fn test_error_position() {
+        // ReqInput인데 seq 중간에서 끊긴 메세지
+        let bytes = [204u8, 0x04, 0xd2, 0x00, 0x00];
+        match MessageFromClient::new(&bytes) {
+            Err(ProtocolError::Decode { message, field, offset, source: NetworkError::ShortMsg { expected_length: 4, actual_length: 2 } }) => {
+                assert_eq!((message, field, offset), ("ReqInput", "seq", 3));
+            },
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // 빈 메세지는 유형부터 없다
+        assert!(matches!(MessageFromServer::new(&[]), Err(ProtocolError::Decode { field: "type", offset: 0, .. })));
+
+        // 좌표가 8바이트 단위로 떨어지지 않는 몸통
+        let mut bytes = vec![201u8, 0, 1];
+        bytes.extend([0u8; 16]);
+        bytes.extend([0u8; 5]);
+        assert!(matches!(MessageFromServer::new(&bytes), Err(ProtocolError::Decode { field: "worm_body.positions", offset: 19, .. })));
+
+        // 뒤에 바이트가 더 붙은 고정 길이 메세지
+        assert!(matches!(MessageFromClient::new(&[102, 0, 1, 0xff]), Err(ProtocolError::Decode { field: "end", offset: 3, .. })));
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        use crate::network::message::snapshot::{FoodInfo, ZoneInfo};
+        use crate::network::message::worm_body::WormBody;
+
+        let msg = MessageFromServer::ResSnapshot {
+            map_radius: 2500.0,
+            damage_zones: vec![ZoneInfo { center: (10.0, -20.0), radius: 50.0, damage_per_sec: 30.0 }],
+            worm_bodies: vec![
+                WormBody::from_parts(1, (1.0, 0.0, 0.0, 1.0), vec![(1.0, 2.0), (3.0, 4.0)]),
+                WormBody::from_parts(2, (0.0, 1.0, 0.0, 1.0), vec![(5.0, 6.0)]),
+            ],
+            foods: vec![FoodInfo { id: 9, position: (7.0, 8.0), growth: 3 }; 3],
+        };
+
+        // 몸통 뒤에 다른 필드가 이어지므로 협상된 형식에서도 몸통 경계가 맞아야 한다.
+        let format = WireFormat::negotiated(Capabilities::SUPPORTED, 2500.0);
+        let bytes = msg.make_message_bytes_with(&format);
+        match MessageFromServer::new_with(&bytes, &format) {
+            Ok(MessageFromServer::ResSnapshot { map_radius, damage_zones, worm_bodies, foods }) => {
+                assert_eq!(map_radius, 2500.0);
+                assert_eq!(damage_zones, vec![ZoneInfo { center: (10.0, -20.0), radius: 50.0, damage_per_sec: 30.0 }]);
+                assert_eq!(worm_bodies.iter().map(|w| (w.client_id(), w.positions().len())).collect::<Vec<_>>(), vec![(1, 2), (2, 1)]);
+                assert_eq!(foods, vec![FoodInfo { id: 9, position: (7.0, 8.0), growth: 3 }; 3]);
+            },
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // 몸통 길이가 남은 바이트보다 길면 그 위치를 알려준다
+        let mut bytes = msg.make_message_bytes_with(&WireFormat::FULL);
+        bytes.truncate(30);
+        assert!(matches!(MessageFromServer::new(&bytes), Err(ProtocolError::Decode { field: "worm_body.length", offset: 25, .. })));
+    }
+
+    #[test]
+    fn test_snapshot_frames_fit_u16() {
+        use crate::network::message::snapshot::{FoodInfo, ZoneInfo};
+        use crate::network::message::worm_body::WormBody;
+
+        // 몸통 하나가 약 40KB, 먹이가 약 130KB라 한 프레임에는 들어가지 않는다
+        let worm_bodies = (0..3).map(|id| WormBody::from_parts(id, (1.0, 1.0, 1.0, 1.0), vec![(1.0, 2.0); 5000])).collect::<Vec<_>>();
+        let foods = (0..10_000).map(|id| FoodInfo { id, position: (3.0, 4.0), growth: 1 }).collect::<Vec<_>>();
+        let zone = ZoneInfo { center: (0.0, 0.0), radius: 50.0, damage_per_sec: 30.0 };
+        let frames = MessageFromServer::snapshot_frames(2500.0, vec![zone], worm_bodies, foods);
+
+        assert!(matches!(frames[0], MessageFromServer::ResSnapshot { .. }));
+        let (mut worm_count, mut food_count) = (0, 0);
+        for (i, frame) in frames.iter().enumerate() {
+            for format in [WireFormat::FULL, WireFormat::negotiated(Capabilities::SUPPORTED, 2500.0)] {
+                assert!(frame.make_message_bytes_with(&format).len() <= u16::MAX as usize);
+            }
+            match frame {
+                MessageFromServer::ResSnapshot { worm_bodies, foods, .. } if i == 0 => {
+                    worm_count += worm_bodies.len();
+                    food_count += foods.len();
+                },
+                MessageFromServer::ResEnterView { worm_bodies, foods } if i > 0 => {
+                    worm_count += worm_bodies.len();
+                    food_count += foods.len();
+                },
+                other => panic!("unexpected frame: {:?}", other),
+            }
+        }
+        assert_eq!((worm_count, food_count), (3, 10_000));
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        // 몸 길이는 u16을 넘을 수 있다
+        let max_points = u16::MAX as usize + 10;
+        let msg = MessageFromServer::ResState {
+            client_id: 1,
+            input_seq: 7,
+            head: (1.0, 2.0),
+            dir: (1.0, 0.0),
+            boost_remaining: 1.5,
+            boost_available: true,
+            max_points,
+        };
+        let bytes = msg.make_message_bytes_with(&WireFormat::FULL);
+        assert_eq!(bytes.len(), 32);
+        match MessageFromServer::new(&bytes) {
+            Ok(MessageFromServer::ResState { client_id, max_points: decoded, input_seq, .. }) => {
+                assert_eq!((client_id, decoded, input_seq), (1, max_points, 7));
+            },
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_move_delta_round_trip() {
+        let length = u16::MAX as usize + 10;
+        let msg = MessageFromServer::ResMoveDelta { client_id: 3, length, new_positions: vec![(1.0, 2.0)] };
+        let bytes = msg.make_message_bytes_with(&WireFormat::FULL);
+        match MessageFromServer::new(&bytes) {
+            Ok(MessageFromServer::ResMoveDelta { client_id, length: decoded, new_positions }) => {
+                assert_eq!((client_id, decoded, new_positions), (3, length, vec![(1.0, 2.0)]));
+            },
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_room_list_round_trip() {
+        use crate::network::message::room::RoomInfo;
+
+        let rooms = vec![
+            RoomInfo { name: "default".to_string(), players: 3, max_players: 100, map_radius: 2500.0 },
+            RoomInfo { name: "작은 방".to_string(), players: 1, max_players: 4, map_radius: 800.0 },
+        ];
+        let bytes = MessageFromServer::ResRoomList { rooms: rooms.clone() }.make_message_bytes_with(&WireFormat::FULL);
+        match MessageFromServer::new(&bytes) {
+            Ok(MessageFromServer::ResRoomList { rooms: decoded }) => assert_eq!(decoded, rooms),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // 이름 길이가 남은 바이트보다 길면 그 위치를 알려준다
+        assert!(matches!(MessageFromServer::new(&bytes[..8]), Err(ProtocolError::Decode { field: "rooms", offset: 4, .. })));
+    }
+
+    #[test]
+    fn test_shutdown_round_trip() {
+        for restart_in_secs in [None, Some(0), Some(30)] {
+            let msg = MessageFromServer::ResShutdown { restart_in_secs, reason: "점검".to_string() };
+            let bytes = msg.make_message_bytes_with(&WireFormat::FULL);
+            match MessageFromServer::new(&bytes) {
+                Ok(MessageFromServer::ResShutdown { restart_in_secs: decoded, reason }) => {
+                    assert_eq!((decoded, reason.as_str()), (restart_in_secs, "점검"));
+                },
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+    }
+
+    // 유형 필드는 실제 있는 유형이 자주 나오도록 섞어서 만든다.
+    fn message_bytes() -> impl Strategy<Value = Vec<u8>> {
+        (
+            prop_oneof![Just(101u8), Just(102), Just(103), Just(104), Just(105), Just(106), Just(107), Just(108), Just(111), Just(112), Just(113), Just(201), Just(202), Just(203), Just(204), Just(205), Just(206), Just(207), Just(208), Just(209), Just(210), Just(211), Just(212), any::<u8>()],
+            prop::collection::vec(any::<u8>(), 0..96),
+        ).prop_map(|(type_num, body)| [vec![type_num], body].concat())
+    }
+
+    proptest! {
+        #[test]
+        fn decode_never_panics(bytes in message_bytes()) {
+            let _ = MessageFromClient::new(&bytes);
+            let _ = MessageFromServer::new(&bytes);
+
+            // 협상된 형식 (u8 색상, 고정소수점 좌표)
+            let format = WireFormat::negotiated(Capabilities::SUPPORTED, 2500.0);
+            let _ = MessageFromClient::new_with(&bytes, &format);
+            let _ = MessageFromServer::new_with(&bytes, &format);
+        }
+
+        // 스트림으로 들어온 아무 바이트를 코덱으로 끝까지 잘라내도 패닉하지 않아야 한다.
+        #[test]
+        fn codec_never_panics(bytes in prop::collection::vec(any::<u8>(), 0..512)) {
+            let mut server = ServerCodec::new();
+            let mut buffer = BytesMut::from(&bytes[..]);
+            while let Ok(Some(_)) = server.decode(&mut buffer) {}
+
+            let mut client = ClientCodec::new();
+            let mut buffer = BytesMut::from(&bytes[..]);
+            while let Ok(Some(_)) = client.decode(&mut buffer) {}
+        }
+
+        #[test]
+        fn req_input_round_trip(client_id in any::<u16>(), seq in any::<u32>(), x in -1.0f32..1.0, y in -1.0f32..1.0, boost in any::<bool>()) {
+            let msg = MessageFromClient::ReqInput { client_id: client_id as usize, seq, target_dir: (x, y), boost };
+            let bytes = msg.make_bytes().unwrap();
+            match MessageFromClient::new(&bytes[2..]) {
+                Ok(MessageFromClient::ReqInput { client_id: id, seq: s, target_dir, boost: b }) => {
+                    prop_assert_eq!((id, s, target_dir, b), (client_id as usize, seq, (x, y), boost));
+                },
+                other => prop_assert!(false, "unexpected result: {:?}", other),
+            }
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use crate::network::util;
+
+// ResRoomList에 담기는 방 하나의 정보
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoomInfo {
+    pub name: String,
+    pub players: usize,     // 지금 방에 들어와 있는 연결 수
+    pub max_players: usize,
+    pub map_radius: f32,
+}
+
+impl RoomInfo {
+    // 이름은 길이(u8)와 같이 보내므로 이보다 길 수 없다
+    pub const MAX_NAME_LENGTH: usize = u8::MAX as usize;
+
+    // 이름 길이(u8), 이름(UTF-8), 인원(u16), 최대 인원(u16), 맵 반지름(f32)
+    pub fn make_bytes(&self) -> Vec<u8> {
+        let name = &self.name.as_bytes()[..self.name.len().min(Self::MAX_NAME_LENGTH)];
+        let mut bytes = Vec::with_capacity(9 + name.len());
+        bytes.push(name.len() as u8);
+        bytes.extend(name);
+        bytes.extend(util::u16_be_to_bytes(self.players as u16));
+        bytes.extend(util::u16_be_to_bytes(self.max_players as u16));
+        bytes.extend(self.map_radius.to_be_bytes());
+        bytes
+    }
+}
@@ -0,0 +1,32 @@
+use crate::network::util;
+
+// ResSnapshot에 담기는 월드 정보. 지렁이 몸통은 WormBody를 그대로 쓴다.
+
+// 머리가 들어가 있는 동안 몸 길이가 줄어드는 영역
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ZoneInfo {
+    pub center: (f32, f32),
+    pub radius: f32,
+    pub damage_per_sec: f32,
+}
+
+// 먹이 하나
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FoodInfo {
+    pub id: u32,            // 서버가 정한 먹이 id. 먹을 때와 지울 때 쓴다
+    pub position: (f32, f32),
+    pub growth: usize,      // 먹으면 늘어나는 몸 길이
+}
+
+impl FoodInfo {
+    // 먹이 id(u32), 좌표(f32, f32), 성장(u8)
+    pub const SIZE: usize = 13;
+
+    pub fn make_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::SIZE);
+        bytes.extend(util::u32_be_to_bytes(self.id));
+        bytes.extend(util::positions_to_bytes(&[self.position]));
+        bytes.push(self.growth as u8);
+        bytes
+    }
+}
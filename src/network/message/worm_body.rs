@@ -1,8 +1,9 @@
 use crate::network::error::NetworkError;
+use crate::network::protocol::WireFormat;
 use crate::network::util;
 
 // 지렁이는 몸통 요소 좌표들과 색상 rgba를 가짐
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct WormBody {
     client_id: usize,
     color: (f32, f32, f32, f32),
@@ -11,8 +12,11 @@ pub struct WormBody {
 
 impl WormBody {
     pub fn new(client_id: usize, bytes: &[u8]) -> Result<Self, NetworkError> {
-        let color = &bytes[..16];
-        let positions = &bytes[16..];
+        // 색상 16바이트가 없으면 아래 split_at이 패닉하므로 먼저 확인
+        if bytes.len() < 16 {
+            return Err(NetworkError::ShortMsg { expected_length: 16, actual_length: bytes.len() });
+        }
+        let (color, positions) = bytes.split_at(16);
 
         Ok(Self {
             client_id,
@@ -21,6 +25,22 @@ impl WormBody {
         })
     }
 
+    pub fn from_parts(client_id: usize, color: (f32, f32, f32, f32), positions: Vec<(f32, f32)>) -> Self {
+        Self { client_id, color, positions }
+    }
+
+    pub fn client_id(&self) -> usize {
+        self.client_id
+    }
+
+    pub fn color(&self) -> (f32, f32, f32, f32) {
+        self.color
+    }
+
+    pub fn positions(&self) -> &[(f32, f32)] {
+        &self.positions
+    }
+
     pub fn random(client_id: usize) -> Self {
         // todo 색상, 위치 정보를 랜덤하게 만들어야 함
         Self {
@@ -38,11 +58,42 @@ impl WormBody {
     }
 
     pub fn make_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::with_capacity(2 + self.positions.len() * 16);
+        self.make_bytes_with(&WireFormat::FULL)
+    }
+
+    // 어떤 형식으로 써도 이보다 길어지지 않는다. (client id 2, f32 색상 16, 고정소수점 폭과 범위 5, 좌표 하나에 최대 8)
+    pub fn max_bytes_len(&self) -> usize {
+        2 + 16 + 5 + self.positions.len() * 8
+    }
+
+    // 연결마다 협상된 형식으로 색상과 좌표를 쓴다
+    pub fn make_bytes_with(&self, format: &WireFormat) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(2 + 16 + self.positions.len() * 8);
         bytes.push((self.client_id >> 8 & 0xff) as u8);
         bytes.push((self.client_id & 0xff) as u8);
-        bytes.extend(util::color_to_bytes(&self.color));
-        bytes.extend(util::positions_to_bytes(&self.positions));
+        bytes.extend(format.color_to_bytes(&self.color));
+        bytes.extend(format.positions_to_bytes(&self.positions));
         bytes
     }
-}
\ No newline at end of file
+}
+
+// 클라이언트(Bevy)에서 쓰는 Vec2, Srgba와의 변환
+#[cfg(feature = "bevy")]
+impl WormBody {
+    pub fn from_bevy(client_id: usize, color: bevy::color::Srgba, points: impl IntoIterator<Item = bevy::math::Vec2>) -> Self {
+        Self {
+            client_id,
+            color: (color.red, color.green, color.blue, color.alpha),
+            positions: points.into_iter().map(|p| (p.x, p.y)).collect(),
+        }
+    }
+
+    pub fn srgba(&self) -> bevy::color::Srgba {
+        let (r, g, b, a) = self.color;
+        bevy::color::Srgba::new(r, g, b, a)
+    }
+
+    pub fn points(&self) -> Vec<bevy::math::Vec2> {
+        self.positions.iter().map(|&(x, y)| bevy::math::Vec2::new(x, y)).collect()
+    }
+}
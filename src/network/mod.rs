@@ -1,3 +1,7 @@
 pub mod message;
+pub mod codec;
+pub mod protocol;
+pub mod delta;
+pub mod heartbeat;
 pub mod error;
 pub mod util;
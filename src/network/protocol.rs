@@ -0,0 +1,152 @@
+use crate::network::util;
+use std::fmt;
+use std::ops::BitOr;
+
+// 메세지 형식이 바뀌면 올린다. 서버와 클라이언트의 버전이 다르면 서버가 접속을 거절한다.
+//  1: 처음 인사를 도입한 형식
+//  2: ReqJoin이 세션 토큰(u64)을 보내고, 차이 몸통, 고정소수점 좌표, 시야, 방, 서버 종료 메세지가 생김
+//  3: ResWelcome에 서버의 초당 tick 수가 붙음
+//  4: 몸 길이가 65535를 넘을 수 있으므로 ResState의 최대 길이와 ResMoveDelta의 몸 길이를 u32로 늘림
+//  5: ResState에서 몸통을 빼고, 주인도 자기 몸통을 ResMove로 받음
+pub const PROTOCOL_VERSION: u16 = 5;
+
+// 연결마다 협상하는 선택 기능들. 클라이언트가 지원하는 기능을 보내면
+// 서버는 자신도 지원하는 것만 남겨서 돌려주고, 이후 그 연결에서는 남은 기능만 쓴다.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Capabilities(u32);
+
+impl Capabilities {
+    // 지렁이 몸통을 이전 상태와의 차이로 보낸다
+    pub const DELTA_BODIES: Self = Self(1 << 0);
+    // 메세지 본문 압축
+    pub const COMPRESSION: Self = Self(1 << 1);
+    // 색상을 f32 4개 대신 u8 4개로 보낸다
+    pub const COMPACT_COLORS: Self = Self(1 << 2);
+    // 몸통 좌표를 f32 대신 맵 반지름 기준 i16/i24 고정소수점으로 보낸다
+    pub const FIXED_POSITIONS: Self = Self(1 << 3);
+    // 길이 필드를 u16 대신 varint로 써서 64 KiB보다 긴 메세지를 보낸다
+    pub const VARINT_FRAMES: Self = Self(1 << 4);
+
+    // 이 빌드가 실제로 구현한 기능. 기능을 구현하면 여기에 추가한다.
+    pub const SUPPORTED: Self = Self(
+        Self::DELTA_BODIES.0 | Self::COMPACT_COLORS.0 | Self::FIXED_POSITIONS.0 | Self::VARINT_FRAMES.0
+    );
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    // 모르는 비트가 있어도 그대로 들고 있다가 협상할 때 걸러진다.
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    // 양쪽이 모두 지원하는 기능만 남긴다
+    pub const fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+}
+
+impl BitOr for Capabilities {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = [
+            (Self::DELTA_BODIES, "delta_bodies"),
+            (Self::COMPRESSION, "compression"),
+            (Self::COMPACT_COLORS, "compact_colors"),
+            (Self::FIXED_POSITIONS, "fixed_positions"),
+            (Self::VARINT_FRAMES, "varint_frames"),
+        ];
+        let enabled = names.iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| *name)
+            .collect::<Vec<_>>();
+        if enabled.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", enabled.join(", "))
+        }
+    }
+}
+
+// 협상된 기능에 따라 몸통의 색과 좌표를 어떤 형식으로 쓰는지. 코덱이 연결마다 하나씩 들고 있다.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WireFormat {
+    pub compact_colors: bool,
+    pub fixed_positions: bool,
+    // 고정소수점 좌표의 범위를 정할 때만 쓴다. 받는 쪽은 범위가 메세지에 같이 오므로 몰라도 된다.
+    pub map_radius: f32,
+}
+
+impl WireFormat {
+    // 아무 기능도 협상하지 않은 기본 형식 (f32 색상, f32 좌표)
+    pub const FULL: Self = Self { compact_colors: false, fixed_positions: false, map_radius: 0.0 };
+
+    pub fn negotiated(capabilities: Capabilities, map_radius: f32) -> Self {
+        Self {
+            compact_colors: capabilities.contains(Capabilities::COMPACT_COLORS),
+            fixed_positions: capabilities.contains(Capabilities::FIXED_POSITIONS),
+            map_radius,
+        }
+    }
+
+    //  f32 색상: r, g, b, a (f32 x 4) / compact: r, g, b, a (u8 x 4)
+    pub fn color_to_bytes(&self, color: &(f32, f32, f32, f32)) -> Vec<u8> {
+        if self.compact_colors {
+            util::color_to_compact_bytes(color).to_vec()
+        } else {
+            util::color_to_bytes(color)
+        }
+    }
+
+    //  f32 좌표: (f32, f32) 반복
+    //  고정소수점: 좌표 폭(u8, 2 또는 3), 범위(f32), (i16/i24, i16/i24) 반복
+    pub fn positions_to_bytes(&self, positions: &[(f32, f32)]) -> Vec<u8> {
+        if !self.fixed_positions {
+            return util::positions_to_bytes(positions);
+        }
+        let width = util::fixed_position_width(self.map_radius);
+        let range = self.map_radius * util::FIXED_POSITION_RANGE_SCALE;
+        let mut bytes = Vec::with_capacity(5 + positions.len() * width * 2);
+        bytes.push(width as u8);
+        bytes.extend(range.to_be_bytes());
+        bytes.extend(util::positions_to_fixed_bytes(positions, range, width));
+        bytes
+    }
+}
+
+// 서버가 이 버전의 클라이언트를 받을 수 있는지
+pub fn is_compatible(version: u16) -> bool {
+    version == PROTOCOL_VERSION
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiation() {
+        let client = Capabilities::DELTA_BODIES | Capabilities::COMPACT_COLORS | Capabilities::from_bits(1 << 31);
+        let server = Capabilities::DELTA_BODIES | Capabilities::COMPRESSION;
+
+        let negotiated = client.intersection(server);
+        assert_eq!(negotiated, Capabilities::DELTA_BODIES);
+        assert!(!negotiated.contains(Capabilities::COMPACT_COLORS));
+        assert_eq!(negotiated.to_string(), "delta_bodies");
+    }
+}
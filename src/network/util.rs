@@ -14,8 +14,12 @@ pub fn u16_be_to_bytes(num: u16) -> [u8; 2] {
     [(num >> 8 & 0xff) as u8, (num & 0xff) as u8]
 }
 
+pub fn u32_be_to_bytes(num: u32) -> [u8; 4] {
+    num.to_be_bytes()
+}
+
 pub fn bytes_to_hex(bytes: &[u8]) -> String {
-    if bytes.len() == 0 {
+    if bytes.is_empty() {
         return "".to_string()
     }
     format!("0x{}", hex::encode(bytes))
@@ -47,7 +51,7 @@ pub fn color_to_bytes(color: &(f32, f32, f32, f32)) -> Vec<u8> {
 
 // bytes -> positions
 pub fn bytes_to_positions(bytes: &[u8]) -> Result<Vec<(f32, f32)>, NetworkError> {
-    if bytes.len() % 8 != 0 {
+    if !bytes.len().is_multiple_of(8) {
         return Err(NetworkError::InvalidMsg { input_length: bytes.len() });
     }
 
@@ -63,7 +67,7 @@ pub fn bytes_to_positions(bytes: &[u8]) -> Result<Vec<(f32, f32)>, NetworkError>
 }
 
 // positions -> bytes
-pub fn positions_to_bytes(positions: &Vec<(f32, f32)>) -> Vec<u8> {
+pub fn positions_to_bytes(positions: &[(f32, f32)]) -> Vec<u8> {
     let mut bytes = Vec::with_capacity(positions.len() * 8);
 
     for &(a, b) in positions {
@@ -72,4 +76,119 @@ pub fn positions_to_bytes(positions: &Vec<(f32, f32)>) -> Vec<u8> {
     }
 
     bytes
-}
\ No newline at end of file
+}
+// 고정소수점 좌표는 맵 반지름의 2배 범위(-2R..2R)를 정수 범위에 나눠 담는다.
+// 맵 밖으로 조금 나간 지렁이까지 담을 수 있도록 여유를 둔다.
+pub const FIXED_POSITION_RANGE_SCALE: f32 = 2.0;
+// 좌표 오차가 이보다 작으면 i16, 크면 (큰 맵) i24를 쓴다.
+pub const MAX_FIXED_POSITION_ERROR: f32 = 0.1;
+
+// 좌표 하나에 쓰는 바이트 수. 2 = i16, 3 = i24
+pub fn fixed_position_width(map_radius: f32) -> usize {
+    let range = map_radius * FIXED_POSITION_RANGE_SCALE;
+    if range / fixed_max(2) as f32 / 2.0 <= MAX_FIXED_POSITION_ERROR { 2 } else { 3 }
+}
+
+fn fixed_max(width: usize) -> i32 {
+    (1 << (width * 8 - 1)) - 1
+}
+
+// positions -> i16/i24 고정소수점 bytes. 범위를 넘는 좌표는 끝에 붙인다.
+pub fn positions_to_fixed_bytes(positions: &[(f32, f32)], range: f32, width: usize) -> Vec<u8> {
+    let max = fixed_max(width);
+    let mut bytes = Vec::with_capacity(positions.len() * width * 2);
+
+    for &(a, b) in positions {
+        for v in [a, b] {
+            let q = (v / range * max as f32).round().clamp(-max as f32, max as f32) as i32;
+            bytes.extend_from_slice(&q.to_be_bytes()[4 - width..]);
+        }
+    }
+
+    bytes
+}
+
+// i16/i24 고정소수점 bytes -> positions
+pub fn fixed_bytes_to_positions(bytes: &[u8], range: f32, width: usize) -> Result<Vec<(f32, f32)>, NetworkError> {
+    if !(2..=3).contains(&width) || !bytes.len().is_multiple_of(width * 2) {
+        return Err(NetworkError::InvalidMsg { input_length: bytes.len() });
+    }
+
+    let max = fixed_max(width) as f32;
+    let value = |chunk: &[u8]| {
+        // 부호 확장: 맨 앞 바이트의 부호 비트로 빈 앞자리를 채운다
+        let fill = if chunk[0] & 0x80 != 0 { 0xff } else { 0x00 };
+        let mut buf = [fill; 4];
+        buf[4 - width..].copy_from_slice(chunk);
+        i32::from_be_bytes(buf) as f32 / max * range
+    };
+
+    Ok(bytes.chunks_exact(width * 2)
+        .map(|pair| (value(&pair[..width]), value(&pair[width..])))
+        .collect())
+}
+
+// color -> 채널당 u8 bytes. 렌더링은 어차피 8비트 색이므로 손해가 없다.
+pub fn color_to_compact_bytes(color: &(f32, f32, f32, f32)) -> [u8; 4] {
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    [channel(color.0), channel(color.1), channel(color.2), channel(color.3)]
+}
+
+// 채널당 u8 bytes -> color
+pub fn compact_bytes_to_color(bytes: [u8; 4]) -> (f32, f32, f32, f32) {
+    let channel = |c: u8| c as f32 / 255.0;
+    (channel(bytes[0]), channel(bytes[1]), channel(bytes[2]), channel(bytes[3]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_position_error_bound() {
+        let map_radius = 2500.0;
+        let range = map_radius * FIXED_POSITION_RANGE_SCALE;
+
+        for width in [2, 3] {
+            let step = range / fixed_max(width) as f32;
+            let positions = (0..1000)
+                .map(|i| {
+                    let t = i as f32 / 999.0 * 2.0 - 1.0;
+                    (t * range, (t * 7.3).sin() * map_radius)
+                })
+                .collect::<Vec<_>>();
+
+            let bytes = positions_to_fixed_bytes(&positions, range, width);
+            assert_eq!(bytes.len(), positions.len() * width * 2);
+
+            let decoded = fixed_bytes_to_positions(&bytes, range, width).unwrap();
+            for (p, d) in positions.iter().zip(decoded.iter()) {
+                // 반올림 오차는 한 칸의 절반 (+ f32 계산 오차)
+                assert!((p.0 - d.0).abs() <= step * 0.5 + 1e-3, "{:?} -> {:?} (width {})", p, d, width);
+                assert!((p.1 - d.1).abs() <= step * 0.5 + 1e-3, "{:?} -> {:?} (width {})", p, d, width);
+            }
+        }
+
+        // 기본 맵은 i16으로 충분하고, 아주 큰 맵은 i24로 넘어간다
+        assert_eq!(fixed_position_width(map_radius), 2);
+        assert_eq!(fixed_position_width(map_radius * 4.0), 3);
+    }
+
+    #[test]
+    fn test_fixed_position_clamp() {
+        let decoded = fixed_bytes_to_positions(&positions_to_fixed_bytes(&[(1e9, -1e9)], 100.0, 3), 100.0, 3).unwrap();
+        assert_eq!(decoded, vec![(100.0, -100.0)]);
+    }
+
+    #[test]
+    fn test_compact_color_error_bound() {
+        for i in 0..=100 {
+            let c = i as f32 / 100.0;
+            let color = (c, 1.0 - c, c * 0.5, 1.0);
+            let decoded = compact_bytes_to_color(color_to_compact_bytes(&color));
+            for (a, b) in [(color.0, decoded.0), (color.1, decoded.1), (color.2, decoded.2), (color.3, decoded.3)] {
+                assert!((a - b).abs() <= 0.5 / 255.0 + 1e-6);
+            }
+        }
+    }
+}
@@ -0,0 +1,557 @@
+use crate::{spawn_damage_zone, spawn_map_shape, DamageZone, Dots, Map, MapShape, RemoteWorms, Worm};
+use bug::network::codec::{ClientCodec, FrameLength};
+use bug::network::delta::DeltaDecoder;
+use bug::network::heartbeat::{self, Heartbeat};
+use bug::network::protocol::{Capabilities, WireFormat, PROTOCOL_VERSION};
+use bug::network::message::message_from_client::MessageFromClient;
+use bug::network::message::message_from_server::MessageFromServer;
+use bug::network::message::snapshot::{FoodInfo, ZoneInfo};
+use bevy::prelude::*;
+use bug::game::TICK_RATE;
+use bug::game::world::World;
+use futures::{SinkExt, StreamExt};
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{self as tokio_mpsc, UnboundedReceiver, UnboundedSender};
+use tokio_util::codec::Framed;
+
+// 접속할 서버 주소. BUG_SERVER_ADDR 환경변수로 바꿀 수 있다.
+const DEFAULT_SERVER_ADDR: &str = "127.0.0.1:8888";
+// BUG_ROOM 환경변수로 방 이름을 주면, 그 방이 없을 때 이 크기로 만든다
+const ROOM_MAX_PLAYERS: usize = 16;
+// 서버가 응답하지 않을 때 무한히 쌓이지 않도록 보관할 미확인 입력 수
+const MAX_PENDING_INPUTS: usize = 256;
+// 보정 오차가 이보다 크면 (리스폰 등) 부드럽게 따라가지 않고 바로 맞춘다
+const CORRECTION_SNAP_DISTANCE: f32 = 200.0;
+// 보정 오차가 줄어드는 속도 (클수록 빨리 서버 위치로 붙는다)
+const CORRECTION_DECAY_RATE: f32 = 10.0;
+
+/// 서버와 TCP로 통신하면서 내 입력을 보내고, 서버가 움직인 지렁이들을 `Worm`과 `RemoteWorms`에 채워넣는 플러그인.
+/// 소켓 입출력은 별도 스레드의 tokio 런타임에서 하고, Bevy 시스템은 채널만 비우므로 프레임 루프를 막지 않는다.
+pub struct NetworkPlugin;
+
+impl Plugin for NetworkPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<PlayerAction>()
+            .add_message::<WorldUpdate>()
+            .init_resource::<InputHistory>()
+            .init_resource::<Latency>()
+            .add_systems(Startup, (connect, spawn_latency_text))
+            .add_systems(Update, (receive_messages, apply_world_updates.after(receive_messages), send_actions, decay_correction, draw_latency_text, sync_tick_interval))
+            // 이번 tick에 보낸 입력으로 이번 tick의 예측 이동을 해야 서버와 순서가 맞는다.
+            .add_systems(FixedUpdate, send_input.before(crate::move_head));
+    }
+}
+
+/// 게임 로직에서 서버로 알려야 하는 일
+#[derive(Message, Clone, Copy)]
+pub enum PlayerAction {
+    Ate { food_id: u32 },
+    Respawned,
+}
+
+// 서버가 보내준 맵과 먹이의 변화. 엔티티를 만들고 지워야 하므로 apply_world_updates에서 처리한다.
+#[derive(Message)]
+enum WorldUpdate {
+    // 참가 직후 받는 월드 전체
+    Snapshot {
+        map_radius: f32,
+        damage_zones: Vec<ZoneInfo>,
+        foods: Vec<FoodInfo>,
+    },
+    FoodSpawned(Vec<FoodInfo>),
+    FoodRemoved(Vec<u32>),
+}
+
+// 네트워크 스레드에서 Bevy 쪽으로 넘어오는 이벤트
+enum NetworkEvent {
+    Message(MessageFromServer),
+    Latency { rtt: Duration, jitter: Duration },
+    Disconnected(String),
+}
+
+/// 서버와의 왕복 시간. 네트워크 스레드가 pong을 받을 때마다 갱신된다.
+#[derive(Resource, Default)]
+pub struct Latency {
+    pub rtt: Option<Duration>,
+    pub jitter: Duration,
+}
+
+#[derive(Component)]
+struct LatencyText;
+
+// 보냈지만 아직 서버 상태에 반영됐다는 확인을 못 받은 입력들
+#[derive(Resource, Default)]
+struct InputHistory {
+    next_seq: u32,
+    pending: VecDeque<PendingInput>,
+}
+
+struct PendingInput {
+    seq: u32,
+    target_dir: Dir2,
+    boost: bool,
+}
+
+#[derive(Resource)]
+struct Connection {
+    outgoing: UnboundedSender<MessageFromClient>,
+    // Receiver는 Sync가 아니므로 리소스에 넣기 위해 Mutex로 감싼다.
+    incoming: Mutex<Receiver<NetworkEvent>>,
+    connected: bool,
+    // 연결이 끊긴 이유. 화면에 그대로 보여준다.
+    disconnect_reason: Option<String>,
+    // 서버가 ResSession으로 정해준 id와 토큰. 받기 전에는 내 지렁이에 대한 메세지를 보내지 않는다.
+    client_id: Option<usize>,
+    session_token: u64,
+    // 서버 tick 간격. 예측과 입력 전송을 이 간격으로 해야 서버와 결과가 같으므로 ResWelcome으로 받은 값을 쓴다.
+    tick_interval: Duration,
+}
+
+impl Connection {
+    fn send(&self, msg: MessageFromClient) {
+        // 연결이 끊긴 뒤에는 보낼 곳이 없으므로 조용히 버린다.
+        let _ = self.outgoing.send(msg);
+    }
+}
+
+fn connect(mut commands: Commands) {
+    let server_addr = std::env::var("BUG_SERVER_ADDR").unwrap_or_else(|_| DEFAULT_SERVER_ADDR.to_string());
+    let (outgoing_tx, outgoing_rx) = tokio_mpsc::unbounded_channel::<MessageFromClient>();
+    let (incoming_tx, incoming_rx) = mpsc::channel::<NetworkEvent>();
+
+    thread::spawn(move || {
+        let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                let _ = incoming_tx.send(NetworkEvent::Disconnected(format!("failed to start network runtime. {}", e)));
+                return;
+            }
+        };
+        let reason = runtime.block_on(run_connection(server_addr, outgoing_rx, &incoming_tx));
+        let _ = incoming_tx.send(NetworkEvent::Disconnected(reason));
+    });
+
+    let connection = Connection {
+        outgoing: outgoing_tx,
+        incoming: Mutex::new(incoming_rx),
+        connected: true,
+        disconnect_reason: None,
+        client_id: None,
+        session_token: 0,
+        tick_interval: Duration::from_secs_f64(1.0 / TICK_RATE),
+    };
+    // 접속이 끝나기 전에 보낸 메세지는 채널에 쌓여있다가, 접속되면 순서대로 전송된다.
+    // 방 이름을 줬으면 먼저 만들어보고, 이미 있으면 그 방으로 들어간 뒤에 참가한다.
+    if let Ok(name) = std::env::var("BUG_ROOM") {
+        connection.send(MessageFromClient::ReqCreateRoom { map_radius: World::MAP_RADIUS, max_players: ROOM_MAX_PLAYERS, name: name.clone() });
+        connection.send(MessageFromClient::ReqEnterRoom { name });
+    }
+    connection.send(MessageFromClient::ReqJoin { session_token: 0 });
+    commands.insert_resource(connection);
+}
+
+// 네트워크 스레드: 서버와 마찬가지로 코덱으로 감싼 스트림에서 수신과 송신을 select! 로 경쟁시킨다.
+// 연결이 끝난 이유를 돌려준다.
+async fn run_connection(
+    server_addr: String,
+    mut outgoing: UnboundedReceiver<MessageFromClient>,
+    incoming: &Sender<NetworkEvent>,
+) -> String {
+    let stream = match TcpStream::connect(&server_addr).await {
+        Ok(stream) => stream,
+        Err(e) => return format!("failed to connect to {}. {}", server_addr, e),
+    };
+    info!("connected to server. ({})", server_addr);
+    let mut framed = Framed::new(stream, ClientCodec::new());
+
+    // Bevy 쪽에서 보낸 ReqJoin은 채널에 남겨두고, 인사가 끝난 뒤에 보낸다.
+    let hello = MessageFromClient::ReqHello { version: PROTOCOL_VERSION, capabilities: Capabilities::SUPPORTED };
+    if let Err(e) = framed.send(hello).await {
+        return format!("failed to write to server. {}", e);
+    }
+    // 몸통을 차이로 받기로 했다면 여기서 전체 몸통으로 되돌려서 넘긴다.
+    let (capabilities, mut delta) = match framed.next().await {
+        Some(Ok(MessageFromServer::ResWelcome { version, capabilities, tick_rate })) => {
+            info!("handshake completed. (version = {}, capabilities = {}, tick rate = {})", version, capabilities, tick_rate);
+            // 방에 들어가기 전에는 좌표를 보내지 않으므로 기본 맵 크기로 시작하고, 방의 맵 크기를 받으면 바꾼다.
+            framed.codec_mut().set_format(WireFormat::negotiated(capabilities, World::MAP_RADIUS));
+            framed.codec_mut().set_frame_length(FrameLength::negotiated(capabilities));
+            // 예측 간격은 Bevy 쪽에서 서버 tick 수에 맞춘다.
+            let _ = incoming.send(NetworkEvent::Message(MessageFromServer::ResWelcome { version, capabilities, tick_rate }));
+            (capabilities, capabilities.contains(Capabilities::DELTA_BODIES).then(DeltaDecoder::new))
+        },
+        Some(Ok(MessageFromServer::ResReject { version, reason })) => {
+            return format!("server rejected the connection. (server version: {}, client version: {}) {}", version, PROTOCOL_VERSION, reason);
+        },
+        Some(Ok(msg)) => return format!("expected welcome, but received {:?}.", msg),
+        Some(Err(e)) => return format!("failed to read from server. {}", e),
+        None => return "server closed the connection.".to_string(),
+    };
+
+    // 서버도 따로 ping을 보내지만, 화면에 보여줄 왕복 시간은 내 시계로 잰다.
+    let mut heartbeat = Heartbeat::new();
+    let mut ping = tokio::time::interval(heartbeat::PING_INTERVAL);
+
+    loop {
+        tokio::select! {
+            read = framed.next() => {
+                match read {
+                    // 연결 유지 메세지는 Bevy 쪽으로 넘기지 않는다.
+                    Some(Ok(MessageFromServer::ResPing { timestamp })) => {
+                        if let Err(e) = framed.send(MessageFromClient::ReqPong { timestamp }).await {
+                            return format!("failed to write to server. {}", e);
+                        }
+                    },
+                    Some(Ok(MessageFromServer::ResPong { timestamp })) => {
+                        if let Some(rtt) = heartbeat.on_pong(timestamp).and(heartbeat.rtt()) {
+                            let _ = incoming.send(NetworkEvent::Latency { rtt, jitter: heartbeat.jitter() });
+                        }
+                    },
+                    // 서버가 곧 연결을 끊으므로 끊긴 이유로 종료 사유를 넘긴다.
+                    Some(Ok(MessageFromServer::ResShutdown { restart_in_secs, reason })) => {
+                        return match restart_in_secs {
+                            Some(secs) => format!("{} restarting in about {} s.", reason, secs),
+                            None => reason,
+                        };
+                    },
+                    Some(Ok(msg)) => {
+                        // 내가 보내는 좌표의 범위도 지금 방의 맵 크기에 맞춘다.
+                        if let MessageFromServer::ResRoomEntered { map_radius, .. } | MessageFromServer::ResSnapshot { map_radius, .. } = &msg {
+                            framed.codec_mut().set_format(WireFormat::negotiated(capabilities, *map_radius));
+                        }
+                        let msg = match delta.as_mut() {
+                            Some(delta) => match delta.decode(msg) {
+                                Ok(msg) => msg,
+                                Err(e) => {
+                                    // 다음 키프레임에서 다시 맞춰진다.
+                                    warn!("failed to decode delta. {}", e);
+                                    continue;
+                                },
+                            },
+                            None => msg,
+                        };
+                        if incoming.send(NetworkEvent::Message(msg)).is_err() {
+                            return "client is shutting down.".to_string();
+                        }
+                    },
+                    Some(Err(e)) => return format!("failed to read from server. {}", e),
+                    None => return "server closed the connection.".to_string(),
+                }
+            },
+            msg = outgoing.recv() => {
+                // Bevy 쪽 Sender가 사라지면 (앱 종료) None이 된다.
+                let Some(msg) = msg else {
+                    return "client is shutting down.".to_string();
+                };
+                if let Err(e) = framed.send(msg).await {
+                    return format!("failed to write to server. {}", e);
+                }
+            },
+            _ = ping.tick() => {
+                if let Err(e) = framed.send(MessageFromClient::ReqPing { timestamp: heartbeat.timestamp() }).await {
+                    return format!("failed to write to server. {}", e);
+                }
+            },
+        }
+    }
+}
+
+fn receive_messages(
+    time: Res<Time>,
+    mut connection: ResMut<Connection>,
+    mut latency: ResMut<Latency>,
+    mut worm: ResMut<Worm>,
+    mut remote: ResMut<RemoteWorms>,
+    mut history: ResMut<InputHistory>,
+    mut world_updates: MessageWriter<WorldUpdate>,
+) {
+    loop {
+        let event = match connection.incoming.lock().unwrap().try_recv() {
+            Ok(event) => event,
+            Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => break,
+        };
+
+        match event {
+            NetworkEvent::Message(MessageFromServer::ResWelcome { tick_rate, .. }) => {
+                connection.tick_interval = Duration::from_secs_f64(1.0 / tick_rate as f64);
+            },
+            NetworkEvent::Message(MessageFromServer::ResSession { client_id, session_token }) => {
+                info!("joined as client id {}.", client_id);
+                connection.client_id = Some(client_id);
+                connection.session_token = session_token;
+                worm.id = client_id as u64;
+                // 같은 id로 다른 지렁이를 그리고 있었다면 지운다.
+                remote.remove(worm.id);
+            },
+            NetworkEvent::Message(MessageFromServer::ResSnapshot { map_radius, damage_zones, worm_bodies, foods }) => {
+                info!("received world snapshot. (worms = {}, foods = {})", worm_bodies.len(), foods.len());
+                // 내 지렁이는 ResJoin으로 이미 받았다.
+                remote.reset(time.elapsed_secs_f64(), worm_bodies.into_iter()
+                    .filter(|worm_body| worm_body.client_id() as u64 != worm.id)
+                    .map(|worm_body| (worm_body.client_id() as u64, worm_body.srgba().into(), worm_body.points())));
+                world_updates.write(WorldUpdate::Snapshot { map_radius, damage_zones, foods });
+            },
+            NetworkEvent::Message(MessageFromServer::ResFoodSpawn { foods }) => {
+                world_updates.write(WorldUpdate::FoodSpawned(foods));
+            },
+            NetworkEvent::Message(MessageFromServer::ResFoodRemove { food_ids }) => {
+                world_updates.write(WorldUpdate::FoodRemoved(food_ids));
+            },
+            // 서버는 내 화면 근처에 있는 것만 보내준다. 나간 것은 먹힌 게 아니므로 흡수 연출 없이 지운다.
+            NetworkEvent::Message(MessageFromServer::ResEnterView { worm_bodies, foods }) => {
+                for worm_body in worm_bodies.into_iter().filter(|worm_body| worm_body.client_id() as u64 != worm.id) {
+                    remote.upsert(worm_body.client_id() as u64, worm_body.srgba().into(), time.elapsed_secs_f64(), worm_body.points());
+                }
+                world_updates.write(WorldUpdate::FoodSpawned(foods));
+            },
+            NetworkEvent::Message(MessageFromServer::ResLeaveView { client_ids, food_ids }) => {
+                for client_id in client_ids {
+                    remote.remove(client_id as u64);
+                }
+                world_updates.write(WorldUpdate::FoodRemoved(food_ids));
+            },
+            NetworkEvent::Message(msg) => apply_message(msg, time.elapsed_secs_f64(), connection.tick_interval.as_secs_f32(), &mut worm, &mut remote, &mut history),
+            NetworkEvent::Latency { rtt, jitter } => {
+                latency.rtt = Some(rtt);
+                latency.jitter = jitter;
+            },
+            NetworkEvent::Disconnected(reason) => {
+                warn!("disconnected from server. {}", reason);
+                connection.connected = false;
+                connection.disconnect_reason = Some(reason);
+            },
+        }
+    }
+}
+
+fn apply_message(msg: MessageFromServer, now: f64, dt: f32, worm: &mut Worm, remote: &mut RemoteWorms, history: &mut InputHistory) {
+    match msg {
+        MessageFromServer::ResJoin { client_id, worm_body } if client_id as u64 == worm.id => {
+            // 새로 생성된 지렁이에는 이전 입력들을 다시 적용하면 안 된다.
+            history.pending.clear();
+            worm.adopt_spawn(worm_body.points());
+        },
+        // 내 몸통은 바로 그리지 않고, 같은 tick에 뒤따라오는 ResState로 되감을 때 쓴다.
+        MessageFromServer::ResMove { client_id, worm_body } if client_id as u64 == worm.id => {
+            worm.server_points = worm_body.points();
+        },
+        MessageFromServer::ResState { client_id, input_seq, head, dir, boost_remaining, boost_available, max_points } => {
+            if client_id as u64 != worm.id || worm.is_dead {
+                return;
+            }
+            reconcile(worm, history, input_seq, dt, |worm| {
+                worm.rewind(
+                    Vec2::new(head.0, head.1),
+                    Vec2::new(dir.0, dir.1),
+                    boost_remaining,
+                    boost_available,
+                    max_points,
+                );
+            });
+        },
+        MessageFromServer::ResJoin { client_id, worm_body } | MessageFromServer::ResMove { client_id, worm_body } => {
+            remote.upsert(client_id as u64, worm_body.srgba().into(), now, worm_body.points());
+        },
+        // 서버가 내 지렁이를 죽였다. 몸통은 despawn_dead_worm이 지우고, R로 다시 참가한다.
+        MessageFromServer::ResDie { client_id } if client_id as u64 == worm.id => {
+            worm.is_dead = true;
+            history.pending.clear();
+        },
+        MessageFromServer::ResLeave { client_id } | MessageFromServer::ResDie { client_id } => {
+            remote.remove(client_id as u64);
+        },
+        MessageFromServer::ResKill { killer_id, victim_id } => {
+            info!("worm {} was killed by worm {}.", victim_id, killer_id);
+        },
+        // 먹은 만큼은 미리 늘려두었고, 서버가 거절했으면 ResState의 몸 길이로 다시 맞춰진다.
+        MessageFromServer::ResEat { food_id, is_ok, .. } => {
+            if !is_ok {
+                debug!("server rejected eating food {}.", food_id);
+            }
+        },
+        // 인사는 네트워크 스레드에서 끝내고, tick 수는 receive_messages에서 처리한다.
+        MessageFromServer::ResWelcome { .. } => {},
+        MessageFromServer::ResRoomList { rooms } => {
+            for room in rooms {
+                info!("room {}: {}/{} players, map radius {}.", room.name, room.players, room.max_players, room.map_radius);
+            }
+        },
+        // 맵은 ReqJoin 뒤에 오는 ResSnapshot으로 다시 그린다.
+        MessageFromServer::ResRoomEntered { name, .. } => {
+            info!("entered room {}.", name);
+        },
+        // 이미 있는 방을 만들려고 한 경우에도 오므로 경고만 남긴다.
+        MessageFromServer::ResRoomError { reason } => {
+            warn!("room request failed. {}", reason);
+        },
+        // 게임 중에 오는 거절은 서버가 이 연결을 내보낸다는 뜻이다. 곧 연결이 끊긴다.
+        MessageFromServer::ResReject { reason, .. } => {
+            warn!("server is disconnecting us. {}", reason);
+        },
+        // receive_messages에서 처리한다.
+        MessageFromServer::ResSession { .. } => {},
+        // 네트워크 스레드에서 ResMove로 되돌려서 넘겨준다.
+        MessageFromServer::ResMoveDelta { .. } => {},
+        // 네트워크 스레드에서 처리한다.
+        MessageFromServer::ResPing { .. } | MessageFromServer::ResPong { .. } | MessageFromServer::ResShutdown { .. } => {},
+        // receive_messages에서 처리한다.
+        MessageFromServer::ResSnapshot { .. } | MessageFromServer::ResFoodSpawn { .. } | MessageFromServer::ResFoodRemove { .. } => {},
+        MessageFromServer::ResEnterView { .. } | MessageFromServer::ResLeaveView { .. } => {},
+    }
+}
+
+// 스냅샷을 받으면 접속 전에 혼자 그려둔 맵, 데미지 영역, 점들을 지우고 서버가 보내준 것으로 다시 그린다.
+// 그 뒤로는 서버가 tick마다 모아 보내는 먹이 변화만 반영한다.
+fn apply_world_updates(
+    mut commands: Commands,
+    mut world_updates: MessageReader<WorldUpdate>,
+    mut map: ResMut<Map>,
+    mut dots: ResMut<Dots>,
+    map_shapes: Query<Entity, With<MapShape>>,
+    damage_zones: Query<Entity, With<DamageZone>>,
+) {
+    for update in world_updates.read() {
+        match update {
+            WorldUpdate::Snapshot { map_radius, damage_zones: zones, foods } => {
+                map.radius = *map_radius;
+                for entity in map_shapes.iter().chain(damage_zones.iter()) {
+                    commands.entity(entity).despawn();
+                }
+                spawn_map_shape(&mut commands, *map_radius);
+                for zone in zones.iter() {
+                    spawn_damage_zone(&mut commands, Vec2::new(zone.center.0, zone.center.1), zone.radius, zone.damage_per_sec);
+                }
+
+                dots.clear(&mut commands);
+                for food in foods.iter() {
+                    dots.spawn_food(&mut commands, food);
+                }
+            },
+            WorldUpdate::FoodSpawned(foods) => {
+                for food in foods.iter() {
+                    dots.spawn_food(&mut commands, food);
+                }
+            },
+            WorldUpdate::FoodRemoved(food_ids) => {
+                for food_id in food_ids.iter() {
+                    dots.remove_food(&mut commands, *food_id);
+                }
+            },
+        }
+    }
+}
+
+// 서버 상태로 되감고, 서버가 아직 반영하지 않은 입력을 순서대로 다시 적용한다.
+// 그 사이 화면에 보이던 위치와의 차이는 correction으로 남겨서 조금씩 줄인다.
+// dt는 입력 하나가 적용된 서버 tick 간격이다.
+fn reconcile(worm: &mut Worm, history: &mut InputHistory, input_seq: u32, dt: f32, rewind: impl FnOnce(&mut Worm)) {
+    while history.pending.front().is_some_and(|input| input.seq <= input_seq) {
+        history.pending.pop_front();
+    }
+
+    let displayed_head = worm.head + worm.correction;
+    let (target_dir, boost_input) = (worm.target_dir, worm.boost_input);
+
+    rewind(worm);
+
+    for input in history.pending.iter() {
+        worm.target_dir = input.target_dir;
+        worm.boost_input = input.boost;
+        worm.simulate(dt);
+    }
+    worm.target_dir = target_dir;
+    worm.boost_input = boost_input;
+
+    let error = displayed_head - worm.head;
+    worm.correction = if error.length() > CORRECTION_SNAP_DISTANCE { Vec2::ZERO } else { error };
+}
+
+fn decay_correction(time: Res<Time>, mut worm: ResMut<Worm>) {
+    if worm.correction == Vec2::ZERO {
+        return;
+    }
+
+    let decay = (-CORRECTION_DECAY_RATE * time.delta_secs()).exp();
+    worm.correction *= decay;
+    if worm.correction.length_squared() < 0.01 {
+        worm.correction = Vec2::ZERO;
+    }
+}
+
+// 매 고정 tick마다 목표 방향과 부스트 키 상태를 번호를 붙여 보내고, 확인받을 때까지 기억해둔다.
+fn send_input(connection: Res<Connection>, worm: Res<Worm>, mut history: ResMut<InputHistory>) {
+    let Some(client_id) = connection.client_id else {
+        return;
+    };
+    if !connection.connected || worm.is_dead {
+        return;
+    }
+
+    history.next_seq = history.next_seq.wrapping_add(1);
+    let seq = history.next_seq;
+    history.pending.push_back(PendingInput { seq, target_dir: worm.target_dir, boost: worm.boost_input });
+    while history.pending.len() > MAX_PENDING_INPUTS {
+        history.pending.pop_front();
+    }
+
+    let target_dir = worm.target_dir.as_vec2();
+    connection.send(MessageFromClient::ReqInput {
+        client_id,
+        seq,
+        target_dir: (target_dir.x, target_dir.y),
+        boost: worm.boost_input,
+    });
+}
+
+fn send_actions(connection: Res<Connection>, mut actions: MessageReader<PlayerAction>) {
+    for action in actions.read() {
+        match (*action, connection.client_id) {
+            (PlayerAction::Ate { food_id }, Some(client_id)) => connection.send(MessageFromClient::ReqEat { client_id, food_id }),
+            // 같은 연결에서 다시 참가하면 서버가 같은 id로 다시 생성해준다.
+            (PlayerAction::Respawned, _) => connection.send(MessageFromClient::ReqJoin { session_token: connection.session_token }),
+            (PlayerAction::Ate { .. }, None) => {},
+        }
+    }
+}
+
+fn spawn_latency_text(mut commands: Commands) {
+    commands.spawn((
+        Text::new("Ping: -"),
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(16.0),
+            top: Val::Px(16.0),
+            ..default()
+        },
+        LatencyText,
+    ));
+}
+
+// FixedUpdate에서 도는 예측 이동과 입력 전송을 서버 tick 간격에 맞춘다.
+fn sync_tick_interval(connection: Res<Connection>, mut fixed_time: ResMut<Time<Fixed>>) {
+    if fixed_time.timestep() != connection.tick_interval {
+        fixed_time.set_timestep(connection.tick_interval);
+    }
+}
+
+fn draw_latency_text(connection: Res<Connection>, latency: Res<Latency>, mut q: Query<&mut Text, With<LatencyText>>) {
+    if !latency.is_changed() && !connection.is_changed() {
+        return;
+    }
+
+    if let Some(mut text) = q.iter_mut().next() {
+        *text = match (connection.connected, latency.rtt) {
+            (false, _) => match &connection.disconnect_reason {
+                Some(reason) => Text::new(format!("Disconnected: {}", reason)),
+                None => Text::new("Disconnected"),
+            },
+            (true, None) => Text::new("Ping: -"),
+            (true, Some(rtt)) => Text::new(format!("Ping: {} ms (±{} ms)", rtt.as_millis(), latency.jitter.as_millis())),
+        };
+    }
+}
@@ -0,0 +1,138 @@
+use bug::game::world::{World, WorldSettings};
+use bug::network::message::room::RoomInfo;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+// 접속하면 처음 들어가는 방. 비어도 닫지 않는다.
+pub const DEFAULT_ROOM: &str = "default";
+// 설정으로 정할 수 있는 방 하나의 최대 인원
+pub const MAX_PLAYERS: usize = 100;
+// 방 이름 최대 바이트 수
+const MAX_NAME_LENGTH: usize = 32;
+// 맵 반지름의 최소값. 이보다 작으면 지렁이가 생성되자마자 맵 밖으로 나간다.
+pub const MIN_MAP_RADIUS: f32 = 500.0;
+// 설정하지 않았을 때 맵 반지름의 최대값
+pub const DEFAULT_MAX_MAP_RADIUS: f32 = 10_000.0;
+
+// 이름이 붙은 게임 월드 하나. 방마다 맵 크기, 먹이, 지렁이가 따로 있고 tick 루프도 따로 돈다.
+pub struct Room {
+    pub name: String,
+    pub map_radius: f32,
+    pub max_players: usize,
+    pub world: Mutex<World>,
+}
+
+#[derive(Debug, Error)]
+pub enum RoomError {
+    #[error("Room name must be 1 to {MAX_NAME_LENGTH} bytes without control characters.")]
+    InvalidName,
+    #[error("Map radius must be between {MIN_MAP_RADIUS} and {max_map_radius}. (map radius: {map_radius})")]
+    InvalidMapRadius { map_radius: f32, max_map_radius: f32 },
+    #[error("Max players must be between 1 and {limit}. (max players: {max_players})")]
+    InvalidMaxPlayers { max_players: usize, limit: usize },
+    #[error("Room already exists. (name: {name})")]
+    AlreadyExists { name: String },
+    #[error("Room does not exist. (name: {name})")]
+    NotFound { name: String },
+    #[error("Room is full. (name: {name}, max players: {max_players})")]
+    Full { name: String, max_players: usize },
+}
+
+// 열려있는 방들. 연결 태스크마다 들고 있는 방은 Arc로 나눠 갖고,
+// 마지막 연결이 나가면 여기서 빼서 tick 루프도 같이 멈추게 한다.
+#[derive(Clone)]
+pub struct Rooms {
+    rooms: Arc<Mutex<HashMap<String, RoomEntry>>>,
+    // 새 방은 맵 크기만 요청대로 바꾸고 먹이 수, 데미지 영역은 서버 설정을 따른다
+    settings: WorldSettings,
+    // 클라이언트가 만드는 방도 이 인원, 맵 크기를 넘을 수 없다
+    max_players: usize,
+    max_map_radius: f32,
+}
+
+struct RoomEntry {
+    room: Arc<Room>,
+    players: usize,     // 이 방에 들어와 있는 연결 수
+}
+
+impl Rooms {
+    pub fn new(settings: WorldSettings, max_players: usize, max_map_radius: f32) -> Self {
+        Self { rooms: Arc::new(Mutex::new(HashMap::new())), settings, max_players, max_map_radius }
+    }
+
+    // 아무도 없이 설정 그대로 방만 연다. 서버가 시작할 때 기본 방을 만드는 데 쓴다.
+    pub fn open(&self, name: &str) -> Result<Arc<Room>, RoomError> {
+        self.insert(name, self.settings.map_radius, self.max_players, 0)
+    }
+
+    // 클라이언트가 만든 방. 만든 연결이 바로 들어간다.
+    pub fn create(&self, name: &str, map_radius: f32, max_players: usize) -> Result<Arc<Room>, RoomError> {
+        self.insert(name, map_radius, max_players, 1)
+    }
+
+    fn insert(&self, name: &str, map_radius: f32, max_players: usize, players: usize) -> Result<Arc<Room>, RoomError> {
+        if name.is_empty() || name.len() > MAX_NAME_LENGTH || name.chars().any(char::is_control) {
+            return Err(RoomError::InvalidName);
+        }
+        if !(MIN_MAP_RADIUS..=self.max_map_radius).contains(&map_radius) {
+            return Err(RoomError::InvalidMapRadius { map_radius, max_map_radius: self.max_map_radius });
+        }
+        if !(1..=self.max_players).contains(&max_players) {
+            return Err(RoomError::InvalidMaxPlayers { max_players, limit: self.max_players });
+        }
+
+        let mut rooms = self.rooms.lock().unwrap();
+        if rooms.contains_key(name) {
+            return Err(RoomError::AlreadyExists { name: name.to_string() });
+        }
+        let room = Arc::new(Room {
+            name: name.to_string(),
+            map_radius,
+            max_players,
+            world: Mutex::new(World::new(&WorldSettings { map_radius, ..self.settings.clone() })),
+        });
+        rooms.insert(name.to_string(), RoomEntry { room: room.clone(), players });
+        Ok(room)
+    }
+
+    pub fn enter(&self, name: &str) -> Result<Arc<Room>, RoomError> {
+        let mut rooms = self.rooms.lock().unwrap();
+        let Some(entry) = rooms.get_mut(name) else {
+            return Err(RoomError::NotFound { name: name.to_string() });
+        };
+        if entry.players >= entry.room.max_players {
+            return Err(RoomError::Full { name: name.to_string(), max_players: entry.room.max_players });
+        }
+        entry.players += 1;
+        Ok(entry.room.clone())
+    }
+
+    // 마지막 연결이 나간 방은 닫는다. 닫았으면 true.
+    pub fn leave(&self, room: &Room) -> bool {
+        let mut rooms = self.rooms.lock().unwrap();
+        let Some(entry) = rooms.get_mut(&room.name) else {
+            return false;
+        };
+        entry.players = entry.players.saturating_sub(1);
+        if entry.players > 0 || room.name == DEFAULT_ROOM {
+            return false;
+        }
+        rooms.remove(&room.name);
+        true
+    }
+
+    pub fn list(&self) -> Vec<RoomInfo> {
+        let rooms = self.rooms.lock().unwrap();
+        let mut list = rooms.values()
+            .map(|entry| RoomInfo {
+                name: entry.room.name.clone(),
+                players: entry.players,
+                max_players: entry.room.max_players,
+                map_radius: entry.room.map_radius,
+            })
+            .collect::<Vec<_>>();
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+        list
+    }
+}
@@ -1,112 +1,466 @@
-mod network;
+mod config;
+mod hub;
+mod room;
+mod session;
 
-use crate::network::message::message_from_client::MessageFromClient;
-use crate::network::util;
-use crate::network::error::NetworkError;
+use crate::config::{Args, Config};
+use crate::hub::{Hub, Recipient};
+use crate::room::{Room, Rooms, DEFAULT_ROOM};
+use crate::session::Sessions;
+use bug::game::validation::ViolationCounter;
+use bug::game::world::{Death, World};
+use bug::network::message::message_from_client::MessageFromClient;
+use bug::network::codec::{FrameLength, ServerCodec};
+use bug::network::delta::DeltaEncoder;
+use bug::network::heartbeat::{self, Heartbeat};
+use clap::Parser;
+use bug::network::protocol::{self, Capabilities, WireFormat, PROTOCOL_VERSION};
+use bug::network::util;
+use futures::{SinkExt, StreamExt};
+use glam::Vec2;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tracing::{error, info, warn};
-use network::message;
-use crate::network::message::message_from_server::MessageFromServer;
-use crate::network::message::worm_body::WormBody;
+use tokio::io::AsyncWriteExt;
+use tokio::task::JoinSet;
+use tokio::time::MissedTickBehavior;
+use tokio_util::codec::Framed;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+use tracing_subscriber::prelude::*;
+use bug::network::message::message_from_server::MessageFromServer;
+
+// 접속 후 이 시간 안에 ReqHello를 보내지 않으면 끊는다
+const HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+
+// 연결 태스크들과 tick 루프가 공유하는 서버 상태
+#[derive(Clone)]
+struct ServerState {
+    hub: Hub,
+    rooms: Rooms,
+    sessions: Arc<Mutex<Sessions>>,
+    // 이 시간 동안 아무 메세지도 보내지 않은 연결은 끊는다
+    idle_timeout: Duration,
+    // 방마다 도는 tick 루프의 초당 tick 수
+    tick_rate: f64,
+    // 서버가 종료 중이면 취소된다. 연결 태스크들은 이걸 보고 남은 메세지를 보낸 뒤 끝난다.
+    shutdown: CancellationToken,
+}
+
+// 연결 하나가 게임에 참가해서 받은 id와 세션 토큰
+#[derive(Clone, Copy)]
+struct Joined {
+    client_id: usize,
+    session_token: u64,
+}
 
 #[tokio::main]
 async fn main() {
+    // 설정이 잘못되었으면 로그를 켜기 전에 사유를 보여주고 바로 끝낸다.
+    let config = match Config::load(&Args::parse()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("invalid configuration. {}", e);
+            std::process::exit(2);
+        },
+    };
+
     // initialize logging library
     // only needs to be called once in the main function.
-    tracing_subscriber::fmt()
-        .with_target(true)
-        .with_level(true)
-        .with_thread_ids(true)
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer()
+            .with_target(true)
+            .with_level(true)
+            .with_thread_ids(true))
+        .with(config.log_filter().unwrap())
         .init();
 
-    let listen_port: u16 = 8888;
-    let bind_info = format!("0.0.0.0:{}", listen_port);
-    let listener = TcpListener::bind(&bind_info).await.unwrap();
-    info!("server started. listening on {}", bind_info);
-
-    while let Ok((socket, client_access_info)) = listener.accept().await {
-        tokio::spawn(async move {
-            info!("[{}] detected new client.", client_access_info);
-            let _ = handle_client(socket, client_access_info).await;
-            info!("[{}] client disconnected.", client_access_info);
-        });
-    }
+    let listener = TcpListener::bind(config.listen_addr).await.unwrap();
+    info!("server started. listening on {}", config.listen_addr);
+    debug!("config = {:?}", config);
+
+    let state = ServerState {
+        // 모든 연결 태스크가 공유하는 브로드캐스트 허브
+        hub: Hub::new(),
+        rooms: Rooms::new(config.world_settings(), config.max_players, config.world.max_map_radius),
+        sessions: Arc::new(Mutex::new(Sessions::new())),
+        idle_timeout: config.idle_timeout(),
+        tick_rate: config.tick_rate,
+        shutdown: CancellationToken::new(),
+    };
+
+    // 기본 방은 서버가 끝날 때까지 열어둔다. 다른 방은 클라이언트가 만들고, 마지막 사람이 나가면 닫힌다.
+    let default_room = state.rooms.open(DEFAULT_ROOM).unwrap();
+    start_room(&default_room, &state);
+
+    // 종료 신호를 받을 때까지 접속을 받는다. 끝난 연결 태스크는 그때그때 거둬들인다.
+    let mut connections = JoinSet::new();
+    let signal = shutdown_signal();
+    tokio::pin!(signal);
+    let signal_name = loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (socket, client_access_info) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        error!("failed to accept connection. {}", e);
+                        continue;
+                    },
+                };
+                let state = state.clone();
+                connections.spawn(async move {
+                    info!("[{}] detected new client.", client_access_info);
+                    let _ = handle_client(socket, client_access_info, &state).await;
+                    info!("[{}] client disconnected.", client_access_info);
+                });
+            },
+            Some(_) = connections.join_next(), if !connections.is_empty() => {},
+            signal_name = &mut signal => break signal_name,
+        }
+    };
+
+    // 더 이상 접속을 받지 않고, 모두에게 종료를 알린 뒤 연결 태스크들이 남은 메세지를 보내고 끝나기를 기다린다.
     drop(listener);
+    info!("received {}. shutting down. (connections = {})", signal_name, connections.len());
+    let reason = format!("server is shutting down. ({})", signal_name);
+    state.hub.broadcast(MessageFromServer::ResShutdown { restart_in_secs: config.restart_eta_secs, reason });
+    state.shutdown.cancel();
+
+    let closed = tokio::time::timeout(config.shutdown_timeout(), async {
+        while connections.join_next().await.is_some() {}
+    }).await;
+    if closed.is_err() {
+        warn!("timed out waiting for connections to close. aborting the rest. (connections = {})", connections.len());
+        connections.shutdown().await;
+    }
+    info!("server stopped.");
 }
 
-// todo 아래 두 가지 경우를 처리해야 한다.
-//  - 클라이언트에서 패킷이 온 경우 (일반적인 경우)
-//  - 여러 클라이언트에게 응답을 브로드캐스팅해야 하는 경우
-//  cf)
-//   rust channel을 써서 각 클라이언트 스레드에 리시버를 하나씩 두고
-//   tokio의 select! macro를 활용해서, 소켓 수신 future와 리시버 수신 future 간 경쟁하며 각각을 처리
-async fn handle_client(mut stream: TcpStream, client_access_info: SocketAddr) -> Result<(), Box<dyn std::error::Error>> {
-    let mut buffer = Vec::with_capacity(2048);
-    let mut read_packet = [0u8; 1024];
-    let mut eof = false;
-
-    'outer: loop {
-        // 패킷 수신
-        let size = stream.read(&mut read_packet).await?;
-        if size == 0 {
-            // 클라이언트가 먼저 소켓 close하면 0이 된다. (클라이언트가 active closer인 경우)
-            // 서버는 바로 끊어내는 것이 아니라, 혹시 아직 버퍼에 잔여 패킷이 있을 수 있으므로 확인 후 종료되도록 한다.
-            eof = true;
-        } else {
-            // TCP는 메세지 경계가 보장되지 않으므로 새로 들어온 패킷은 일단 버퍼에 쌓아놓고, 로직에서 버퍼를 메세지 단위로 소비한다.
-            // 로직에서는 메세지를 읽어낼 수 있다고 판단된 경우에만 버퍼에서 그만큼 소비하므로 패킷이 쪼개져 들어와도 상관없음.
-            buffer.extend_from_slice(&read_packet[..size]);
+// Ctrl-C(SIGINT)나 SIGTERM을 받을 때까지 기다린다. 받은 신호 이름을 돌려준다.
+async fn shutdown_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate()).expect("failed to install SIGTERM handler.");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => "SIGINT",
+            _ = terminate.recv() => "SIGTERM",
         }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        "Ctrl-C"
+    }
+}
+
+// 방마다 tick 루프를 하나씩 돌린다
+fn start_room(room: &Arc<Room>, state: &ServerState) {
+    info!("room opened. (name = {}, map radius = {}, max players = {})", room.name, room.map_radius, room.max_players);
+    tokio::spawn(run_tick_loop(Arc::downgrade(room), state.hub.clone(), state.tick_rate));
+}
+
+// 일정한 주기로 방의 월드를 진행시키고, 각 지렁이의 최신 몸통을 그 지렁이가 보이는 클라이언트들에게 보낸다.
+// 방이 닫혀서 아무도 Room을 들고 있지 않으면 끝난다.
+async fn run_tick_loop(room: Weak<Room>, hub: Hub, tick_rate: f64) {
+    let dt = (1.0 / tick_rate) as f32;
+    let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / tick_rate));
+    // 서버가 밀렸을 때 놓친 tick을 몰아서 돌리지 않는다.
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+        let Some(room) = room.upgrade() else {
+            break;
+        };
+
+        let (died, moves, states, views) = {
+            let mut world = room.world.lock().unwrap();
+            let died = world.step(dt);
+            for death in died.iter() {
+                match death.killer_id {
+                    Some(killer_id) => info!("worm was killed. (room = {}, id = {}, killer id = {})", room.name, death.client_id, killer_id),
+                    None => info!("worm went out of the map. (room = {}, id = {})", room.name, death.client_id),
+                }
+                world.kill(death.client_id);
+            }
+            world.replenish_food();
+
+            let mut moves = Vec::new();
+            let mut states = Vec::new();
+            for worm in world.alive_worms() {
+                moves.push((worm.client_id, MessageFromServer::ResMove { client_id: worm.client_id, worm_body: worm.to_worm_body() }));
+                // 주인에게는 예측 보정에 필요한 상태를 따로 보낸다.
+                states.push((worm.client_id, MessageFromServer::ResState {
+                    client_id: worm.client_id,
+                    input_seq: worm.last_input_seq,
+                    head: (worm.head.x, worm.head.y),
+                    dir: (worm.dir.x, worm.dir.y),
+                    boost_remaining: worm.boost_remaining,
+                    boost_available: worm.boost_available,
+                    max_points: worm.max_points,
+                }));
+            }
+            (died, moves, states, world.update_interests())
+        };
+
+        // 지렁이마다 그 지렁이가 보이는 클라이언트들
+        let mut viewers: HashMap<usize, HashSet<usize>> = HashMap::new();
+        for view in views.iter() {
+            for worm_id in view.visible_worms.iter() {
+                viewers.entry(*worm_id).or_default().insert(view.client_id);
+            }
+        }
+
+        // 죽은 지렁이의 몸통이 바뀐 먹이는 아래 ResFoodSpawn으로 근처에 있는 클라이언트들에게 나간다.
+        for Death { client_id, killer_id } in died {
+            hub.broadcast_room(&room.name, MessageFromServer::ResDie { client_id });
+            if let Some(killer_id) = killer_id {
+                hub.broadcast_room(&room.name, MessageFromServer::ResKill { killer_id, victim_id: client_id });
+            }
+        }
+
+        // 시야 변화는 클라이언트마다 한 번에 모아서 보낸다. 새로 들어온 지렁이는 ResEnterView에 몸통이 들어있다.
+        for view in views {
+            if !view.left_worms.is_empty() || !view.left_foods.is_empty() {
+                hub.send_to_client_id(view.client_id, MessageFromServer::ResLeaveView { client_ids: view.left_worms, food_ids: view.left_foods });
+            }
+            if !view.eaten_foods.is_empty() {
+                hub.send_to_client_id(view.client_id, MessageFromServer::ResFoodRemove { food_ids: view.eaten_foods });
+            }
+            if !view.spawned_foods.is_empty() {
+                hub.send_to_client_id(view.client_id, MessageFromServer::ResFoodSpawn { foods: view.spawned_foods });
+            }
+            if !view.entered_worms.is_empty() || !view.entered_foods.is_empty() {
+                hub.send_to_client_id(view.client_id, MessageFromServer::ResEnterView { worm_bodies: view.entered_worms, foods: view.entered_foods });
+            }
+        }
+
+        for (client_id, msg) in moves {
+            // 주인은 ResState 앞에 자기 몸통을 받아야 하므로 시야와 상관없이 넣는다. 차이로 바꾸는 건 다른 몸통과 같다.
+            let viewers = viewers.entry(client_id).or_default();
+            viewers.insert(client_id);
+            hub.multicast(viewers, msg);
+        }
+        for (client_id, msg) in states {
+            hub.send_to_client_id(client_id, msg);
+        }
+    }
+}
+
+// 아래 세 가지 경우를 tokio의 select! macro로 경쟁시키며 처리한다.
+//  - 클라이언트에서 메세지가 온 경우 (일반적인 경우)
+//  - 다른 연결 태스크가 허브를 통해 이 클라이언트에게 메세지를 보낸 경우 (브로드캐스트)
+//  - ping 주기가 된 경우. 너무 오래 조용한 연결은 여기서 끊는다.
+// 패킷이 쪼개지거나 붙어서 들어오는 건 코덱이 메세지 단위로 정리해준다.
+async fn handle_client(
+    stream: TcpStream,
+    client_access_info: SocketAddr,
+    state: &ServerState,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let hub = &state.hub;
+    let mut framed = Framed::new(stream, ServerCodec::new());
 
-        // 루프 돌면서, 메세지를 정확히 파싱할 수 없을때까지 버퍼를 소비한다.
-        loop {
-            match message::validate_packet_length(&buffer) {
-                Ok(remaining_byte_size) => {
-                    // actual: 1,2,3,4,5 / expected: 1,2,3 => remaining: 4,5 (2개)
-                    // 맨 앞 2바이트는 메세지 경계를 판단하기 위한 길이 필드. 로직에서는 필요없으므로 버린다.
-                    let message_bytes = buffer.drain(..buffer.len() - remaining_byte_size)
-                        .skip(2).collect::<Vec<u8>>();
-                    info!("[{}] message bytes = {}", client_access_info, util::bytes_to_hex(&message_bytes));
-
-                    // 클라이언트에서 온 메세지
-                    let result = MessageFromClient::new(&message_bytes);
-                    if let Err(e) = &result {
-                        error!("{:?}", e);
+    // 버전이 맞는 클라이언트만 허브에 등록한다. 그 전에는 브로드캐스트도 받지 않는다.
+    let Some(capabilities) = handshake(&mut framed, client_access_info, state.tick_rate).await else {
+        if let Err(e) = framed.into_inner().shutdown().await {
+            warn!("[{}] failed to shutdown stream. {}", client_access_info, e);
+        }
+        return Ok(());
+    };
+    info!("[{}] handshake completed. (capabilities = {})", client_access_info, capabilities);
+    // ResWelcome까지는 u16 길이 필드로 오갔고, 그 다음 프레임부터 협상한 길이 필드를 쓴다.
+    framed.codec_mut().set_frame_length(FrameLength::negotiated(capabilities));
+    // 인사하는 동안 종료가 시작됐으면 종료 알림을 받지 못했으므로 여기서 알려주고 끝낸다.
+    if state.shutdown.is_cancelled() {
+        let _ = framed.send(MessageFromServer::ResShutdown { restart_in_secs: None, reason: "server is shutting down.".to_string() }).await;
+        return Ok(());
+    }
+
+    // 처음에는 기본 방에 들어간다. ReqCreateRoom이나 ReqEnterRoom으로 옮길 수 있다.
+    let mut room = match state.rooms.enter(DEFAULT_ROOM) {
+        Ok(room) => room,
+        Err(e) => {
+            warn!("[{}] rejecting client. {}", client_access_info, e);
+            let _ = framed.send(MessageFromServer::ResReject { version: PROTOCOL_VERSION, reason: e.to_string() }).await;
+            return Ok(());
+        },
+    };
+    // 고정소수점 좌표의 범위는 들어가 있는 방의 맵 크기로 정한다. 받는 쪽은 범위를 메세지에서 읽는다.
+    framed.codec_mut().set_format(WireFormat::negotiated(capabilities, room.map_radius));
+    let mut outbound = hub.register(client_access_info, &room.name);
+    // 허브는 모든 연결에 같은 ResMove를 보내고, 차이로 바꾸는 건 협상한 연결만 각자 한다.
+    let mut delta = capabilities.contains(Capabilities::DELTA_BODIES).then(DeltaEncoder::new);
+
+    // 이 연결로 게임에 참여한 세션. 나가기 요청 없이 끊기면 다른 클라들에게 대신 알려준다.
+    let mut joined: Option<Joined> = None;
+
+    // read가 0을 돌려주지 않고 조용히 죽은 연결도 알아챌 수 있도록 주기적으로 ping을 보낸다.
+    let mut heartbeat = Heartbeat::new();
+    let mut ping = tokio::time::interval(heartbeat::PING_INTERVAL);
+    ping.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut last_received = Instant::now();
+    // ReqMove로 말이 안 되는 몸통을 계속 보내는 클라이언트는 내보낸다.
+    let mut violations = ViolationCounter::new();
+
+    loop {
+        tokio::select! {
+            // 메세지 수신
+            read = framed.next() => {
+                let msg = match read {
+                    Some(Ok(msg)) => msg,
+                    Some(Err(e)) => {
+                        error!("[{}] failed to read message. {}", client_access_info, e);
+                        break;
+                    },
+                    // 클라이언트가 먼저 소켓을 close한 경우. 코덱이 버퍼에 남은 메세지는 모두 넘겨준 뒤에 끝난다.
+                    None => break,
+                };
+                debug!("[{}] message = {:?}", client_access_info, msg);
+                last_received = Instant::now();
+
+                // 연결 유지 메세지와 방 목록은 게임 상태와 상관없으므로 허브를 거치지 않고 바로 처리한다.
+                let entered = match msg {
+                    MessageFromClient::ReqPing { timestamp } => {
+                        if let Err(e) = framed.send(MessageFromServer::ResPong { timestamp }).await {
+                            error!("[{}] failed to write to stream. {}", client_access_info, e);
+                            break;
+                        }
+                        continue;
+                    },
+                    MessageFromClient::ReqPong { timestamp } => {
+                        if heartbeat.on_pong(timestamp).is_some() {
+                            debug!("[{}] rtt = {:?}, jitter = {:?}", client_access_info, heartbeat.rtt(), heartbeat.jitter());
+                        }
                         continue;
+                    },
+                    MessageFromClient::ReqRoomList => {
+                        if let Err(e) = framed.send(MessageFromServer::ResRoomList { rooms: state.rooms.list() }).await {
+                            error!("[{}] failed to write to stream. {}", client_access_info, e);
+                            break;
+                        }
+                        continue;
+                    },
+                    MessageFromClient::ReqCreateRoom { map_radius, max_players, ref name } => {
+                        let created = state.rooms.create(name, map_radius, max_players);
+                        if let Ok(created) = &created {
+                            start_room(created, state);
+                        }
+                        Some(created)
+                    },
+                    MessageFromClient::ReqEnterRoom { ref name } if *name == room.name => Some(Ok(room.clone())),
+                    MessageFromClient::ReqEnterRoom { ref name } => Some(state.rooms.enter(name)),
+                    _ => None,
+                };
+
+                // 방을 만들거나 옮긴 경우. 실패하면 원래 방에 그대로 남는다.
+                if let Some(entered) = entered {
+                    let response = match entered {
+                        Ok(entered) => {
+                            if !Arc::ptr_eq(&entered, &room) {
+                                leave_room(&client_access_info, joined, &room, state);
+                                room = entered;
+                                hub.bind_room(&client_access_info, &room.name);
+                                framed.codec_mut().set_format(WireFormat::negotiated(capabilities, room.map_radius));
+                                // 이전 방에서 밀려있던 메세지와 몸통 기준은 버린다. 새 방의 지렁이는 ReqJoin 뒤에 처음부터 받는다.
+                                while outbound.try_recv().is_ok() {}
+                                delta = capabilities.contains(Capabilities::DELTA_BODIES).then(DeltaEncoder::new);
+                                info!("[{}] client entered the room. (name = {})", client_access_info, room.name);
+                            }
+                            MessageFromServer::ResRoomEntered { map_radius: room.map_radius, name: room.name.clone() }
+                        },
+                        Err(e) => {
+                            warn!("[{}] failed to change room. {}", client_access_info, e);
+                            MessageFromServer::ResRoomError { reason: e.to_string() }
+                        },
+                    };
+                    if let Err(e) = framed.send(response).await {
+                        error!("[{}] failed to write to stream. {}", client_access_info, e);
+                        break;
                     }
-                    let msg = result?;
+                    continue;
+                }
 
-                    // 클라이언트의 메세지에 따라 서버 응답을 생성하여 전송
-                    // todo 응답 메세지 유형에 따라, 모든 유저들에게 브로드캐스트할지 해당 클라이언트에게만 응답할지 분기되어야 함.
-                    let response_bytes = process_message(msg, &client_access_info).make_bytes();
-                    info!("[{}] response bytes = {}", client_access_info, util::bytes_to_hex(&response_bytes));
-                    let _ = stream.write_all(&response_bytes).await;
-                },
-                // 패킷이 아직 부족한 경우에는 아무것도 하지 않음. 필요한 경우, 얼마나 부족한지 로깅할 수 있음.
-                Err(NetworkError::TooShortMsg) | Err(NetworkError::ShortMsg { .. }) => break,
-                err @ _ => {
-                    // 필요하면 다른 오류 타입도 추가.
-                    error!("[{}] unexpected situation. (error: {:?})", client_access_info, err);
-                    break 'outer;
+                // 다른 플레이어의 id로 온 메세지는 처리하지 않는다.
+                if let Some(client_id) = msg.client_id()
+                    && joined.is_none_or(|joined| joined.client_id != client_id)
+                {
+                    warn!("[{}] rejected message for client id not owned by this connection. (id = {}, owned = {:?})",
+                        client_access_info, client_id, joined.map(|joined| joined.client_id));
+                    continue;
                 }
-            }
+
+                // 클라이언트의 메세지에 따라 서버 응답을 생성하고, 응답 유형에 따라 허브로 보낼 대상을 정한다.
+                for (response, recipient) in process_message(msg, &client_access_info, &mut joined, &mut violations, &room, state) {
+                    debug!("[{}] response = {:?} ({:?})", client_access_info, response, recipient);
+                    hub.dispatch(&client_access_info, recipient, response);
+                }
+                hub.bind_client_id(&client_access_info, joined.map(|joined| joined.client_id));
+
+                if violations.is_exceeded() {
+                    warn!("[{}] kicking client. (violations = {})", client_access_info, violations.count());
+                    let reason = "too many implausible moves.".to_string();
+                    let _ = framed.send(MessageFromServer::ResReject { version: PROTOCOL_VERSION, reason }).await;
+                    break;
+                }
+            },
+            // 허브에서 전달된 메세지 송신
+            Some(msg) = outbound.recv() => {
+                let msg = match delta.as_mut() {
+                    Some(delta) => delta.encode(&msg),
+                    None => Cow::Borrowed(&*msg),
+                };
+                if let Err(e) = framed.send(&*msg).await {
+                    error!("[{}] failed to write to stream. {}", client_access_info, e);
+                    break;
+                }
+            },
+            // 종료 알림은 허브로 이미 들어와 있으므로 아래에서 남은 메세지와 함께 보내고 끝낸다.
+            _ = state.shutdown.cancelled() => {
+                info!("[{}] closing connection for server shutdown.", client_access_info);
+                break;
+            },
+            _ = ping.tick() => {
+                if last_received.elapsed() >= state.idle_timeout {
+                    info!("[{}] client timed out. (idle for {:?})", client_access_info, last_received.elapsed());
+                    break;
+                }
+                if let Err(e) = framed.send(MessageFromServer::ResPing { timestamp: heartbeat.timestamp() }).await {
+                    error!("[{}] failed to write to stream. {}", client_access_info, e);
+                    break;
+                }
+            },
         }
+    }
+
+    // 게임에서 나가지 않은 채로 연결이 끊긴 경우, 다른 클라들이 지렁이를 지울 수 있도록 대신 알려준다.
+    hub.unregister(&client_access_info);
+    if let Some(Joined { client_id, session_token }) = joined {
+        info!("[{}] client dropped without leaving. (id = {}, rtt = {:?})", client_access_info, client_id, heartbeat.rtt());
+        // 재접속하면 토큰으로 같은 id를 다시 받을 수 있도록 세션은 잠시 남겨둔다.
+        state.sessions.lock().unwrap().disconnect(session_token);
+    }
+    leave_room(&client_access_info, joined, &room, state);
 
-        if eof {
+    // 연결 종료 전, 아직 소켓에 쓰지 못한 메세지를 마저 보낸다.
+    while let Ok(msg) = outbound.try_recv() {
+        if framed.send(&*msg).await.is_err() {
             break;
         }
     }
 
     // 버퍼가 아직 남아있음에도 통신을 종료하게되는 경우에는 남은 버퍼를 로깅
-    if !buffer.is_empty() {
+    if !framed.read_buffer().is_empty() {
         // bytes to hex str
-        error!("[{}] dropping incomplete buffer. (buffer = {})", client_access_info, util::bytes_to_hex(&buffer));
+        error!("[{}] dropping incomplete buffer. (buffer = {})", client_access_info, util::bytes_to_hex(framed.read_buffer()));
     }
 
     // 명확하게 소켓을 종료 처리
     // FIN
+    let mut stream = framed.into_inner();
     if let Err(e) = stream.shutdown().await {
         warn!("[{}] failed to shutdown stream. {}", client_access_info, e);
     }
@@ -116,26 +470,165 @@ async fn handle_client(mut stream: TcpStream, client_access_info: SocketAddr) ->
     Ok(())
 }
 
-fn process_message(msg: MessageFromClient, client_access_info: &SocketAddr) -> MessageFromServer {
+// 방을 옮기거나 연결이 끊길 때. 지렁이를 그 방의 월드에서 지우고 남은 사람들에게 알려준다.
+// 마지막 사람이었으면 방을 닫는다.
+fn leave_room(client_access_info: &SocketAddr, joined: Option<Joined>, room: &Room, state: &ServerState) {
+    if let Some(Joined { client_id, .. }) = joined
+        && room.world.lock().unwrap().remove(client_id).is_some()
+    {
+        state.hub.broadcast_room(&room.name, MessageFromServer::ResLeave { client_id });
+    }
+    if state.rooms.leave(room) {
+        info!("[{}] room closed. (name = {})", client_access_info, room.name);
+    }
+}
+
+// 접속 직후 ReqHello를 기다려서 프로토콜 버전을 확인하고, 양쪽이 지원하는 기능만 남겨 ResWelcome으로 돌려준다.
+// 클라이언트가 같은 간격으로 예측하도록 tick 수도 같이 알려준다.
+// 버전이 다르거나 다른 메세지가 먼저 오면 ResReject로 사유를 알려주고 None.
+async fn handshake(framed: &mut Framed<TcpStream, ServerCodec>, client_access_info: SocketAddr, tick_rate: f64) -> Option<Capabilities> {
+    let reason = match tokio::time::timeout(HELLO_TIMEOUT, framed.next()).await {
+        Ok(Some(Ok(MessageFromClient::ReqHello { version, capabilities }))) => {
+            if protocol::is_compatible(version) {
+                let capabilities = capabilities.intersection(Capabilities::SUPPORTED);
+                let welcome = MessageFromServer::ResWelcome { version: PROTOCOL_VERSION, capabilities, tick_rate: tick_rate as f32 };
+                if let Err(e) = framed.send(welcome).await {
+                    error!("[{}] failed to write to stream. {}", client_access_info, e);
+                    return None;
+                }
+                return Some(capabilities);
+            }
+            format!("incompatible protocol version. (client: {}, server: {})", version, PROTOCOL_VERSION)
+        },
+        Ok(Some(Ok(msg))) => format!("expected hello, but received {:?}.", msg),
+        Ok(Some(Err(e))) => format!("failed to read hello. {}", e),
+        // 인사도 없이 끊은 경우에는 보낼 곳이 없다
+        Ok(None) => return None,
+        Err(_) => "timed out waiting for hello.".to_string(),
+    };
+
+    warn!("[{}] rejecting client. {}", client_access_info, reason);
+    let _ = framed.send(MessageFromServer::ResReject { version: PROTOCOL_VERSION, reason }).await;
+    None
+}
+
+// 새 플레이어의 등장, 퇴장은 모든 플레이어가 알아야 하므로 브로드캐스트한다.
+// 참가한 본인에게는 ResJoin 뒤에 월드 전체 상태(ResSnapshot)를 따로 보낸다.
+// 이동은 tick 루프가 월드를 진행시킨 결과를 브로드캐스트하므로 여기서는 응답하지 않는다.
+// id는 서버가 나눠주고, 세션 토큰은 참가한 본인에게만 보낸다.
+fn process_message(
+    msg: MessageFromClient,
+    client_access_info: &SocketAddr,
+    joined: &mut Option<Joined>,
+    violations: &mut ViolationCounter,
+    room: &Room,
+    state: &ServerState,
+) -> Vec<(MessageFromServer, Recipient)> {
+    let mut world = room.world.lock().unwrap();
+
     match msg {
-        MessageFromClient::ReqJoin { client_id} => {
+        MessageFromClient::ReqJoin { session_token } => {
+            let mut responses = Vec::new();
+            // 이미 참가한 연결이라면 죽은 뒤 다시 참가하는 경우이므로 같은 id로 새 위치에 다시 생성한다.
+            // 살아있는데 다시 참가하는 것은 순간이동이므로 무시하고 위반으로 센다.
+            let client_id = match *joined {
+                Some(Joined { client_id, .. }) => client_id,
+                None => {
+                    let Some((client_id, session_token)) = state.sessions.lock().unwrap().join(session_token) else {
+                        warn!("[{}] no client id left to assign.", client_access_info);
+                        return vec![];
+                    };
+                    *joined = Some(Joined { client_id, session_token });
+                    responses.push((MessageFromServer::ResSession { client_id, session_token }, Recipient::Sender));
+                    client_id
+                },
+            };
+            let Some(worm) = world.respawn(client_id) else {
+                warn!("[{}] rejected join while the worm is alive. (id = {})", client_access_info, client_id);
+                violations.record(Instant::now());
+                return responses;
+            };
             info!("[{}] client joined to the game. (id = {})", client_access_info, client_id);
-            MessageFromServer::ResJoin { client_id, worm_body: WormBody::random(client_id) }
+            let worm_body = worm.to_worm_body();
+            // 다른 클라이언트들은 새 지렁이가 시야에 들어올 때 ResEnterView로 받는다.
+            responses.push((MessageFromServer::ResJoin { client_id, worm_body }, Recipient::Sender));
+            // 중간에 들어온 클라이언트도 맵과 주변의 지렁이, 먹이를 바로 그릴 수 있도록 스냅샷을 보내준다.
+            responses.extend(snapshot(&mut world, client_id).into_iter().map(|msg| (msg, Recipient::Sender)));
+            responses
+        },
+        MessageFromClient::ReqHello { .. } => {
+            warn!("[{}] hello after handshake is ignored.", client_access_info);
+            vec![]
         },
+        // 연결 태스크에서 처리한다.
+        MessageFromClient::ReqPing { .. }
+        | MessageFromClient::ReqPong { .. }
+        | MessageFromClient::ReqRoomList
+        | MessageFromClient::ReqCreateRoom { .. }
+        | MessageFromClient::ReqEnterRoom { .. } => vec![],
         MessageFromClient::ReqLeave { client_id } => {
             info!("[{}] client leaved to the game. (id = {})", client_access_info, client_id);
-            MessageFromServer::ResLeave { client_id }
+            world.remove(client_id);
+            if let Some(Joined { session_token, .. }) = joined.take() {
+                state.sessions.lock().unwrap().leave(session_token);
+            }
+            vec![(MessageFromServer::ResLeave { client_id }, Recipient::All)]
         },
         MessageFromClient::ReqMove { client_id, worm_body } => {
-            info!("[{}] client moved in the game. (id = {}, positions = {:?})",
+            // 입력 대신 몸통을 직접 보고하는 클라이언트. 다음 tick에 다른 지렁이들과 함께 브로드캐스트된다.
+            debug!("[{}] client reported its body. (id = {}, positions = {:?})",
                      client_access_info, client_id, worm_body);
-            MessageFromServer::ResMove { client_id, worm_body }
+            let now = Instant::now();
+            match world.report_body(&worm_body, now) {
+                None => warn!("[{}] move from unknown worm. (id = {})", client_access_info, client_id),
+                Some(Ok(())) => {},
+                Some(Err(violation)) => {
+                    let action = if violation.is_clamped() { "clamped" } else { "rejected" };
+                    warn!("[{}] {} implausible move. (id = {}) {}", client_access_info, action, client_id, violation);
+                    violations.record(now);
+                },
+            }
+            vec![]
         },
-        MessageFromClient::ReqEat { .. } => {
-            todo!()
+        MessageFromClient::ReqInput { client_id, seq, target_dir, boost } => {
+            if !world.apply_input(client_id, seq, Vec2::new(target_dir.0, target_dir.1), boost) {
+                warn!("[{}] input from unknown worm. (id = {})", client_access_info, client_id);
+            }
+            vec![]
         },
-        MessageFromClient::ReqDie { .. } => {
-            todo!()
+        MessageFromClient::ReqEat { client_id, food_id } => {
+            // 실제로 머리 근처에 있는 먹이만 먹게 해준다. 없어진 먹이는 다음 tick에 모두에게 알려준다.
+            let food_amount = world.eat(client_id, food_id);
+            if food_amount.is_none() {
+                debug!("[{}] rejected eating. (id = {}, food id = {})", client_access_info, client_id, food_id);
+            }
+            vec![(MessageFromServer::ResEat {
+                client_id,
+                food_id,
+                food_amount: food_amount.unwrap_or(0),
+                is_ok: food_amount.is_some(),
+            }, Recipient::Sender)]
+        },
+        MessageFromClient::ReqDie { client_id } => {
+            // 몸통은 먹이가 되어 다음 tick에 모두에게 뿌려진다. 세션은 그대로 두므로 다시 ReqJoin을 보내면 같은 id로 부활한다.
+            if !world.kill(client_id) {
+                warn!("[{}] die from unknown worm. (id = {})", client_access_info, client_id);
+                return vec![];
+            }
+            info!("[{}] worm died. (id = {})", client_access_info, client_id);
+            vec![(MessageFromServer::ResDie { client_id }, Recipient::All)]
         },
     }
 }
+
+// 맵 전체와, 막 참가한 지렁이의 시야 안에 있는 것들. 그 다음부터는 tick마다 시야 변화만 보낸다.
+// varint 길이를 협상하지 않은 연결에도 보낼 수 있도록 u16 크기의 프레임 여러 개로 나눠서 돌려준다.
+fn snapshot(world: &mut World, client_id: usize) -> Vec<MessageFromServer> {
+    let view = world.view(client_id).unwrap_or_default();
+    MessageFromServer::snapshot_frames(
+        world.map_radius,
+        world.damage_zones.iter().map(|zone| zone.to_zone_info()).collect(),
+        view.entered_worms,
+        view.entered_foods,
+    )
+}
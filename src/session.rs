@@ -0,0 +1,81 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+// 연결이 끊긴 뒤에도 이 시간 동안은 토큰으로 같은 id를 되찾을 수 있다
+const SESSION_TTL: Duration = Duration::from_secs(60);
+
+// 서버가 나눠준 플레이어 id와 세션 토큰.
+// 토큰은 추측할 수 없는 난수이고, 참가한 본인에게만 알려준다.
+#[derive(Default)]
+pub struct Sessions {
+    sessions: HashMap<u64, Session>,
+    next_id: usize,
+}
+
+struct Session {
+    client_id: usize,
+    disconnected_at: Option<Instant>,   // 연결이 살아있으면 None
+}
+
+impl Sessions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 끊겨 있는 세션의 토큰이면 그 id를 돌려주고, 아니면 새 id와 토큰을 만든다.
+    // id가 다 찼으면 None.
+    pub fn join(&mut self, session_token: u64) -> Option<(usize, u64)> {
+        self.prune();
+
+        // 이미 살아있는 연결이 쓰고 있는 토큰이면 새 세션으로 취급한다.
+        if let Some(session) = self.sessions.get_mut(&session_token)
+            && session.disconnected_at.is_some()
+        {
+            session.disconnected_at = None;
+            return Some((session.client_id, session_token));
+        }
+
+        let client_id = self.allocate_id()?;
+        let session_token = loop {
+            // 0은 "토큰 없음"으로 쓰므로 제외
+            let token = rand::random::<u64>();
+            if token != 0 && !self.sessions.contains_key(&token) {
+                break token;
+            }
+        };
+        self.sessions.insert(session_token, Session { client_id, disconnected_at: None });
+        Some((client_id, session_token))
+    }
+
+    // 연결이 끊겼을 때. 잠시 id를 비워두고 재접속을 기다린다.
+    pub fn disconnect(&mut self, session_token: u64) {
+        if let Some(session) = self.sessions.get_mut(&session_token) {
+            session.disconnected_at = Some(Instant::now());
+        }
+    }
+
+    // 게임에서 나간 경우. id를 바로 돌려놓는다.
+    pub fn leave(&mut self, session_token: u64) {
+        self.sessions.remove(&session_token);
+    }
+
+    fn prune(&mut self) {
+        self.sessions.retain(|_, session| {
+            session.disconnected_at.is_none_or(|at| at.elapsed() < SESSION_TTL)
+        });
+    }
+
+    // 프로토콜의 client id가 u16이므로 1..=u16::MAX 안에서 쓰고 있지 않은 id를 돌아가며 찾는다.
+    // 0은 클라이언트에서 "아직 id 없음"으로 쓴다.
+    fn allocate_id(&mut self) -> Option<usize> {
+        let used = self.sessions.values().map(|session| session.client_id).collect::<HashSet<_>>();
+        let max_id = u16::MAX as usize;
+        for _ in 0..max_id {
+            self.next_id = self.next_id % max_id + 1;
+            if !used.contains(&self.next_id) {
+                return Some(self.next_id);
+            }
+        }
+        None
+    }
+}